@@ -0,0 +1,322 @@
+use crate::processing::production_line::{ClickResult, ProductionLine};
+use crate::production::inventory::Inventory;
+use crate::production::resource::{setup_resource_manager, Resource, ResourceManager};
+use crate::production::upgrades::{UpgradeError, Upgrades};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// The version of the save format written by [`GameState::save`]. Saves of other versions aren't
+/// loaded.
+pub const SAVE_VERSION: u32 = 1;
+
+/// Everything needed to pick a game back up: the resources, what the player holds, the
+/// production lines running, and the upgrades bought.
+pub struct GameState {
+    pub resources: ResourceManager,
+    pub inventory: Inventory,
+    pub lines: Vec<ProductionLine>,
+    pub upgrades: Upgrades,
+}
+
+#[derive(Serialize)]
+struct SaveRef<'a> {
+    version: u32,
+    resources: Vec<&'a Resource>,
+    discovered: Vec<u64>,
+    inventory: &'a Inventory,
+    lines: &'a [ProductionLine],
+    upgrades: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+struct Save {
+    version: u32,
+    resources: Vec<Resource>,
+    discovered: Vec<u64>,
+    inventory: Inventory,
+    lines: Vec<ProductionLine>,
+    #[serde(default)]
+    upgrades: Vec<u64>,
+}
+
+impl GameState {
+    pub fn new(resources: ResourceManager) -> Self {
+        GameState {
+            resources,
+            inventory: Inventory::new(),
+            lines: vec![],
+            upgrades: Upgrades::new(),
+        }
+    }
+
+    /// Buys an upgrade, paying for it from `inventory`
+    pub fn buy_upgrade(&mut self, id: u64, inventory: &mut Inventory) -> Result<(), UpgradeError> {
+        self.upgrades.buy(id, inventory)
+    }
+
+    /// Clicks a production line, with its crafts multiplied by the bought upgrades. Gives `None`
+    /// if there's no line at that index.
+    pub fn click(&mut self, line: usize) -> Option<ClickResult> {
+        let upgrades = &self.upgrades;
+        let line = self.lines.get_mut(line)?;
+        Some(line.click_multiplied(|id| upgrades.click_multiplier(id)))
+    }
+
+    /// Writes the game to a JSON file. Transformers can't be saved, so only the resources they
+    /// created are written, and loading sets the transformers up again. Likewise only which
+    /// upgrades were bought is written, and the upgrades on offer have to be added again.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let save = SaveRef {
+            version: SAVE_VERSION,
+            resources: self.resources.resources().collect(),
+            discovered: self
+                .resources
+                .visible_resources()
+                .into_iter()
+                .map(Resource::id)
+                .collect(),
+            inventory: &self.inventory,
+            lines: &self.lines,
+            upgrades: self.upgrades.bought().collect(),
+        };
+        let file =
+            File::create(path).map_err(|e| format!("Couldn't create {}: {}", path.display(), e))?;
+        serde_json::to_writer(BufWriter::new(file), &save)
+            .map_err(|e| format!("Couldn't write the game to {}: {}", path.display(), e))
+    }
+
+    /// Loads a game saved with [`save`](Self::save) from a game set up by [`setup_resource_manager`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<GameState, String> {
+        Self::load_with(path, setup_resource_manager)
+    }
+
+    /// Loads a saved game, rebuilding the resources and transformers with `setup`, then putting
+    /// back every saved resource as it was saved, including ones added or changed after setup.
+    /// Resources set up that aren't in the save, such as ones removed since, are removed again. A
+    /// saved resource whose id is set up as a different resource, or whose name is set up under a
+    /// different id, means the save is from a different setup, so it isn't loaded.
+    pub fn load_with<P, F>(path: P, setup: F) -> Result<GameState, String>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&mut ResourceManager) -> Result<(), String>,
+    {
+        let path = path.as_ref();
+        let file =
+            File::open(path).map_err(|e| format!("Couldn't open {}: {}", path.display(), e))?;
+        let save: Save = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("Couldn't read a game from {}: {}", path.display(), e))?;
+        if save.version != SAVE_VERSION {
+            return Err(format!(
+                "Save version {} isn't supported, expected {}",
+                save.version, SAVE_VERSION
+            ));
+        }
+
+        let mut resources = ResourceManager::new();
+        setup(&mut resources)?;
+        let saved_ids = save
+            .resources
+            .iter()
+            .map(Resource::id)
+            .collect::<HashSet<_>>();
+        for saved in save.resources {
+            let name = saved.name().clone();
+            resources.restore_resource(saved).map_err(|e| {
+                format!(
+                    "{} in the save doesn't match the resources set up: {}",
+                    name, e
+                )
+            })?;
+        }
+        let removed = resources
+            .resources()
+            .map(Resource::id)
+            .filter(|id| !saved_ids.contains(id))
+            .collect::<Vec<_>>();
+        for id in removed {
+            resources.remove_resource(id);
+        }
+        for id in save.discovered {
+            resources.discover(id);
+        }
+
+        let mut upgrades = Upgrades::new();
+        for id in save.upgrades {
+            upgrades.restore_bought(id);
+        }
+
+        Ok(GameState {
+            resources,
+            inventory: save.inventory,
+            lines: save.lines,
+            upgrades,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::recipe::{Recipe, RecipeComponent};
+    use crate::production::resource::{ResourceBuilder, ResourceTag};
+    use crate::production::upgrades::{Upgrade, UpgradeEffect};
+    use crate::test_util::TempDir;
+    use std::time::Duration;
+
+    fn game() -> GameState {
+        let mut resources = ResourceManager::new();
+        setup_resource_manager(&mut resources).unwrap();
+        let iron = resources.resource_id_by_name("Iron").unwrap();
+        let ingot = resources.resource_id_by_name("Iron Ingot").unwrap();
+        let mut game = GameState::new(resources);
+        game.inventory.set(iron, 12);
+        game.inventory.set(ingot, 3);
+
+        let smelt = Recipe::new(
+            vec![RecipeComponent::new(iron, 2)],
+            vec![RecipeComponent::new(ingot, 1)],
+            4000,
+            vec![],
+        );
+        let mut line = ProductionLine::new(smelt, Inventory::new());
+        line.inventory_mut().set(iron, 4);
+        line.tick(Duration::from_millis(5000));
+        game.lines.push(line);
+        game
+    }
+
+    #[test]
+    fn game_round_trips() {
+        let mut game = game();
+        let wire = game.resources.resource_id_by_name("Copper Wire").unwrap();
+        game.resources.remove_resource(wire);
+        let plate = game.resources.resource_id_by_name("Gold Plate").unwrap();
+        game.resources.discover(plate);
+
+        let dir = TempDir::new("save");
+        let path = dir.join("save.json");
+        game.save(&path).unwrap();
+        let loaded = GameState::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.inventory, game.inventory);
+        assert_eq!(loaded.lines, game.lines);
+        assert_eq!(
+            loaded.resources.resources().collect::<Vec<_>>(),
+            game.resources.resources().collect::<Vec<_>>()
+        );
+        assert!(loaded.resources.resource_by_name("Copper Wire").is_none());
+        assert!(loaded.resources.is_visible(plate));
+    }
+
+    #[test]
+    fn upgrades_multiply_clicks() {
+        let mut game = game();
+        let iron = game.resources.resource_id_by_name("Iron").unwrap();
+        let ingot = game.resources.resource_id_by_name("Iron Ingot").unwrap();
+        let smelt = Recipe::new(
+            vec![RecipeComponent::new(iron, 2)],
+            vec![RecipeComponent::new(ingot, 1)],
+            4000,
+            vec![],
+        );
+        let mut line = ProductionLine::new(smelt, Inventory::new())
+            .with_click_amount(Duration::from_millis(4000))
+            .with_click_cooldown(Duration::from_millis(0));
+        line.inventory_mut().set(iron, 6);
+        game.lines = vec![line];
+        let multiply = |cost, multiplier| Upgrade {
+            cost: vec![RecipeComponent::new(iron, cost)],
+            effect: UpgradeEffect::ClickMultiplier {
+                resource_id: ingot,
+                multiplier,
+            },
+        };
+        game.upgrades.insert(0, multiply(10, 2.0));
+        game.upgrades.insert(1, multiply(2, 1.5));
+        let crafted = |quantity| {
+            Some(ClickResult::Crafted(vec![RecipeComponent::new(
+                ingot, quantity,
+            )]))
+        };
+
+        assert_eq!(game.click(0), crafted(1));
+        let mut wallet = game.inventory.clone();
+        game.buy_upgrade(0, &mut wallet).unwrap();
+        assert_eq!(wallet.quantity(iron), 2);
+        assert_eq!(game.click(0), crafted(2));
+        game.buy_upgrade(1, &mut wallet).unwrap();
+        assert_eq!(game.click(0), crafted(3));
+        assert_eq!(game.lines[0].inventory().quantity(ingot), 6);
+        assert_eq!(game.click(1), None);
+
+        let dir = TempDir::new("upgraded_save");
+        let path = dir.join("save.json");
+        game.save(&path).unwrap();
+        let loaded = GameState::load(&path).unwrap();
+        assert_eq!(loaded.upgrades.bought().collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn resources_added_after_setup_are_loaded() {
+        let mut game = game();
+        let tin = ResourceBuilder::new()
+            .name("Tin")
+            .tags(vec![ResourceTag::Metal, ResourceTag::Base])
+            .build()
+            .unwrap();
+        let tin = game.resources.add_resource(tin).unwrap();
+        let iron = game.resources.resource_id_by_name("Iron").unwrap();
+        game.resources
+            .update_resource(iron, |iron| *iron = iron.clone().with_symbol("Fe"))
+            .unwrap();
+
+        let dir = TempDir::new("extended_save");
+        let path = dir.join("save.json");
+        game.save(&path).unwrap();
+        let mut loaded = GameState::load(&path).unwrap();
+
+        assert_eq!(
+            loaded.resources.resources().collect::<Vec<_>>(),
+            game.resources.resources().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.resources.resource_id_by_name("Tin"), Some(tin));
+        assert!(loaded
+            .resources
+            .resource_by_name("Tin Ingot")
+            .unwrap()
+            .is_derived());
+        assert_eq!(
+            loaded.resources.resource_by_id(iron).unwrap().symbol(),
+            Some("Fe")
+        );
+
+        // New resources don't take the ids of restored ones
+        let zinc = ResourceBuilder::new().name("Zinc").build().unwrap();
+        let zinc = loaded.resources.add_resource(zinc).unwrap();
+        assert!(game.resources.resource_by_id(zinc).is_none());
+    }
+
+    #[test]
+    fn conflicting_ids_are_an_error() {
+        let dir = TempDir::new("mismatched_save");
+        let path = dir.join("save.json");
+        game().save(&path).unwrap();
+        // Tin takes the id iron was saved with
+        let loaded = GameState::load_with(&path, |resources| {
+            let tin = ResourceBuilder::new().name("Tin").build().unwrap();
+            resources.add_resource(tin).map_err(|e| e.to_string())?;
+            setup_resource_manager(resources)
+        });
+        assert_eq!(
+            loaded.err(),
+            Some(
+                "Iron in the save doesn't match the resources set up: Id 0 is already used by Tin"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use crate::production::resource::Resource;
+use iced::image::Handle;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Keeps the icons of resources once they're read from disk, so drawing them every frame doesn't
+/// read them again. Resources without an icon, and icons that can't be read, all get the same
+/// blank fallback handle.
+pub struct IconCache {
+    base_dir: PathBuf,
+    handles: RefCell<HashMap<PathBuf, Handle>>,
+    fallback: Handle,
+}
+
+impl IconCache {
+    /// Creates an empty cache which finds icons relative to `base_dir`
+    pub fn new<P: AsRef<Path>>(base_dir: P) -> Self {
+        IconCache {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            handles: RefCell::new(HashMap::new()),
+            // A single transparent pixel, in BGRA
+            fallback: Handle::from_pixels(1, 1, vec![0, 0, 0, 0]),
+        }
+    }
+
+    /// The handle given for resources whose icon can't be shown
+    pub fn fallback(&self) -> &Handle {
+        &self.fallback
+    }
+
+    /// How many icon paths have been read, including ones that couldn't be and got the fallback
+    pub fn len(&self) -> usize {
+        self.handles.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.borrow().is_empty()
+    }
+
+    /// The handle for a resource's icon, read from disk the first time the icon is asked for. A
+    /// missing icon is remembered as the fallback too, so it isn't looked for again.
+    pub fn handle_for(&self, resource: &Resource) -> Handle {
+        let icon = resource.base_icon();
+        if icon.as_os_str().is_empty() {
+            return self.fallback.clone();
+        }
+        let path = self.base_dir.join(icon);
+        self.handles
+            .borrow_mut()
+            .entry(path)
+            .or_insert_with_key(|path| match std::fs::read(path) {
+                Ok(bytes) => Handle::from_memory(bytes),
+                Err(_) => self.fallback.clone(),
+            })
+            .clone()
+    }
+
+    /// Forgets every icon read, so they're read again the next time they're asked for, such as
+    /// after they've changed on disk
+    pub fn clear(&self) {
+        self.handles.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use iced::Color;
+
+    fn resource(icon: &str) -> Resource {
+        Resource::new("Iron", "", icon, Color::WHITE, Color::BLACK, &[])
+    }
+
+    #[test]
+    fn icons_are_read_once() {
+        let dir = TempDir::new("icon_cache");
+        std::fs::write(dir.join("iron.png"), b"\x89PNG\r\n\x1a\nrest of the image").unwrap();
+
+        let cache = IconCache::new(dir.path());
+        let iron = resource("iron.png");
+        let first = cache.handle_for(&iron);
+        std::fs::remove_file(dir.join("iron.png")).unwrap();
+        let second = cache.handle_for(&iron);
+        assert_eq!(first.id(), second.id());
+        assert_ne!(first.id(), cache.fallback().id());
+        assert_eq!(cache.len(), 1);
+
+        let missing = resource("copper.png");
+        assert_eq!(cache.handle_for(&missing).id(), cache.fallback().id());
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert_eq!(cache.handle_for(&iron).id(), cache.fallback().id());
+    }
+
+    #[test]
+    fn empty_icon_gets_the_fallback() {
+        let cache = IconCache::new(std::env::temp_dir());
+        assert_eq!(cache.handle_for(&resource("")).id(), cache.fallback().id());
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod icon_cache;
+pub mod recipe_browser;
+pub mod resource_list;
@@ -0,0 +1,280 @@
+use crate::processing::recipe::{Recipe, RecipeComponent};
+use crate::processing::recipe_loader::RecipeKey;
+use crate::production::resource::ResourceManager;
+use iced::{
+    button, executor, scrollable, text_input, Application, Button, Clipboard, Column, Command,
+    Container, Element, Length, Row, Scrollable, Text, TextInput,
+};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Shows the recipe at the index in [`RecipeBrowser::recipes`]
+    SelectRecipe(usize),
+    /// Filters the recipe list by the search box's new contents
+    Search(String),
+    /// Clears the search box, listing every recipe again
+    ClearSearch,
+}
+
+/// A window listing recipes on the left, filtered by a search box, and showing the selected
+/// recipe's components with their resource names on the right
+pub struct RecipeBrowser {
+    manager: ResourceManager,
+    recipes: Vec<(RecipeKey, Recipe)>,
+    search: String,
+    /// Indices of the recipes matching the search, in order
+    visible: Vec<usize>,
+    selected: Option<usize>,
+    search_input: text_input::State,
+    clear_button: button::State,
+    list_scroll: scrollable::State,
+    /// One per recipe, so a recipe keeps its button state while the search changes
+    recipe_buttons: Vec<button::State>,
+}
+
+impl RecipeBrowser {
+    pub fn new(manager: ResourceManager, recipes: Vec<(RecipeKey, Recipe)>) -> Self {
+        let recipe_buttons = recipes.iter().map(|_| button::State::new()).collect();
+        let mut browser = RecipeBrowser {
+            manager,
+            recipes,
+            search: String::new(),
+            visible: vec![],
+            selected: None,
+            search_input: text_input::State::new(),
+            clear_button: button::State::new(),
+            list_scroll: scrollable::State::new(),
+            recipe_buttons,
+        };
+        browser.filter();
+        browser
+    }
+
+    pub fn recipes(&self) -> &[(RecipeKey, Recipe)] {
+        &self.recipes
+    }
+
+    /// The indices of the recipes listed, which are those matching the search
+    pub fn visible(&self) -> &[usize] {
+        &self.visible
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn search(&self) -> &str {
+        &self.search
+    }
+
+    /// Applies a message to the browser, without needing a window. Selecting an index past the
+    /// last recipe is ignored.
+    pub fn handle(&mut self, message: Message) {
+        match message {
+            Message::SelectRecipe(index) => {
+                if index < self.recipes.len() {
+                    self.selected = Some(index);
+                }
+            }
+            Message::Search(search) => {
+                self.search = search;
+                self.filter();
+            }
+            Message::ClearSearch => {
+                self.search.clear();
+                self.filter();
+            }
+        }
+    }
+
+    /// Lists the recipes whose key contains the search, ignoring case, or which use a resource
+    /// [`ResourceManager::search`] finds. An empty search lists every recipe.
+    fn filter(&mut self) {
+        let query = self.search.trim().to_lowercase();
+        if query.is_empty() {
+            self.visible = (0..self.recipes.len()).collect();
+            return;
+        }
+        let found = self
+            .manager
+            .search(&query)
+            .into_iter()
+            .map(|resource| resource.id())
+            .collect::<HashSet<_>>();
+        self.visible = self
+            .recipes
+            .iter()
+            .enumerate()
+            .filter(|(_, (key, recipe))| {
+                key.to_lowercase().contains(&query) || found.iter().any(|&id| recipe.references(id))
+            })
+            .map(|(index, _)| index)
+            .collect();
+    }
+
+    /// Names the resource of a component, falling back to its id if it no longer exists
+    fn describe(manager: &ResourceManager, component: &RecipeComponent) -> String {
+        let name = manager.resource_by_id(component.resource_id()).map_or_else(
+            || format!("#{}", component.resource_id()),
+            |res| res.name().clone(),
+        );
+        if component.chance() < 1.0 {
+            format!(
+                "{} x {} ({:.0}%)",
+                component.quantity(),
+                name,
+                component.chance() * 100.0
+            )
+        } else {
+            format!("{} x {}", component.quantity(), name)
+        }
+    }
+
+    fn section<'a>(
+        manager: &ResourceManager,
+        title: &str,
+        components: &[RecipeComponent],
+    ) -> Column<'a, Message> {
+        components.iter().fold(
+            Column::new().spacing(4).push(Text::new(title).size(24)),
+            |section, component| section.push(Text::new(Self::describe(manager, component))),
+        )
+    }
+}
+
+impl Application for RecipeBrowser {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Flags = (ResourceManager, Vec<(RecipeKey, Recipe)>);
+
+    fn new((manager, recipes): Self::Flags) -> (Self, Command<Message>) {
+        (RecipeBrowser::new(manager, recipes), Command::none())
+    }
+
+    fn title(&self) -> String {
+        "Recipes".to_string()
+    }
+
+    fn update(&mut self, message: Message, _clipboard: &mut Clipboard) -> Command<Message> {
+        self.handle(message);
+        Command::none()
+    }
+
+    fn view(&mut self) -> Element<'_, Message> {
+        let RecipeBrowser {
+            manager,
+            recipes,
+            search,
+            visible,
+            selected,
+            search_input,
+            clear_button,
+            list_scroll,
+            recipe_buttons,
+        } = self;
+
+        let search_row = Row::new()
+            .spacing(4)
+            .push(
+                TextInput::new(search_input, "Search recipes", search, Message::Search).padding(6),
+            )
+            .push(Button::new(clear_button, Text::new("Clear")).on_press(Message::ClearSearch));
+        let mut list = Scrollable::new(list_scroll).spacing(2).height(Length::Fill);
+        for (index, state) in recipe_buttons.iter_mut().enumerate() {
+            if !visible.contains(&index) {
+                continue;
+            }
+            list = list.push(
+                Button::new(state, Text::new(&recipes[index].0))
+                    .width(Length::Fill)
+                    .on_press(Message::SelectRecipe(index)),
+            );
+        }
+        let left = Column::new()
+            .spacing(8)
+            .width(Length::FillPortion(1))
+            .push(search_row)
+            .push(list);
+
+        let right: Element<'_, Message> = match selected {
+            Some(index) => {
+                let (key, recipe) = &recipes[*index];
+                let mut details = Column::new()
+                    .spacing(12)
+                    .push(Text::new(key.as_str()).size(32))
+                    .push(Text::new(format!("Takes {} ms", recipe.base_time())))
+                    .push(Self::section(manager, "Inputs", recipe.inputs()))
+                    .push(Self::section(manager, "Outputs", recipe.outputs()));
+                if !recipe.catalysts().is_empty() {
+                    details = details.push(Self::section(manager, "Catalysts", recipe.catalysts()));
+                }
+                details.into()
+            }
+            None => Text::new("Select a recipe").into(),
+        };
+
+        Row::new()
+            .spacing(20)
+            .padding(10)
+            .push(left)
+            .push(Container::new(right).width(Length::FillPortion(2)))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::production::resource::setup_resource_manager;
+
+    fn browser() -> RecipeBrowser {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let recipe = |input, output| {
+            Recipe::new(
+                vec![RecipeComponent::new(id(input), 1)],
+                vec![RecipeComponent::new(id(output), 1)],
+                4000,
+                vec![],
+            )
+        };
+        let recipes = vec![
+            (
+                "Smelt copper".to_string(),
+                recipe("Copper Ore", "Copper Ingot"),
+            ),
+            ("Smelt iron".to_string(), recipe("Iron Ore", "Iron Ingot")),
+            ("Draw wire".to_string(), recipe("Iron Ingot", "Iron Wire")),
+        ];
+        RecipeBrowser::new(manager, recipes)
+    }
+
+    #[test]
+    fn selecting_recipes() {
+        let mut browser = browser();
+        assert_eq!(browser.selected(), None);
+        browser.handle(Message::SelectRecipe(1));
+        assert_eq!(browser.selected(), Some(1));
+        browser.handle(Message::SelectRecipe(3));
+        assert_eq!(browser.selected(), Some(1));
+    }
+
+    #[test]
+    fn searching_filters_recipes() {
+        let mut browser = browser();
+        assert_eq!(browser.visible(), &[0, 1, 2]);
+
+        browser.handle(Message::Search("SMELT".to_string()));
+        assert_eq!(browser.visible(), &[0, 1]);
+        browser.handle(Message::Search("iron ingot".to_string()));
+        assert_eq!(browser.visible(), &[1, 2]);
+        browser.handle(Message::Search("tin".to_string()));
+        assert!(browser.visible().is_empty());
+
+        browser.handle(Message::ClearSearch);
+        assert_eq!(browser.search(), "");
+        assert_eq!(browser.visible(), &[0, 1, 2]);
+    }
+}
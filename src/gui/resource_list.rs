@@ -0,0 +1,147 @@
+use crate::production::resource::{Resource, ResourceManager, ResourceOrder};
+use iced::{
+    container, executor, scrollable, Align, Application, Background, Clipboard, Color, Command,
+    Container, Element, Length, Row, Scrollable, Text,
+};
+
+/// How wide and tall the color swatch of a resource is
+const SWATCH_SIZE: u16 = 32;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Rebuilds the rows from the resource manager, such as after resources were added
+    Refresh,
+}
+
+/// What a row shows about a resource
+#[derive(Debug, Clone, PartialEq)]
+struct ResourceRow {
+    name: String,
+    /// Shown in the swatch, which is the symbol if the resource has one
+    label: String,
+    fg_color: Color,
+    bg_color: Color,
+    tags: String,
+}
+
+impl ResourceRow {
+    fn new(resource: &Resource) -> Self {
+        let label = match resource.symbol() {
+            Some(symbol) => symbol.to_string(),
+            None => resource.name().chars().take(2).collect(),
+        };
+        ResourceRow {
+            name: resource.name().clone(),
+            label,
+            fg_color: *resource.fg_color(),
+            bg_color: *resource.bg_color(),
+            tags: resource
+                .tags()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
+/// Draws a swatch in a resource's colors
+struct Swatch {
+    fg_color: Color,
+    bg_color: Color,
+}
+
+impl container::StyleSheet for Swatch {
+    fn style(&self) -> container::Style {
+        container::Style {
+            text_color: Some(self.fg_color),
+            background: Some(Background::Color(self.bg_color)),
+            border_radius: 4.0,
+            ..container::Style::default()
+        }
+    }
+}
+
+/// A window listing every resource by name, with a swatch of its colors and its tags
+pub struct ResourceList {
+    manager: ResourceManager,
+    rows: Vec<ResourceRow>,
+    scroll: scrollable::State,
+}
+
+impl ResourceList {
+    pub fn manager(&self) -> &ResourceManager {
+        &self.manager
+    }
+
+    /// Gives access to the resources, which aren't shown until the next [`Message::Refresh`]
+    pub fn manager_mut(&mut self) -> &mut ResourceManager {
+        &mut self.manager
+    }
+
+    fn refresh(&mut self) {
+        self.rows = self
+            .manager
+            .resources_sorted(ResourceOrder::Name)
+            .into_iter()
+            .map(ResourceRow::new)
+            .collect();
+    }
+}
+
+impl Application for ResourceList {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Flags = ResourceManager;
+
+    fn new(manager: ResourceManager) -> (Self, Command<Message>) {
+        let mut list = ResourceList {
+            manager,
+            rows: vec![],
+            scroll: scrollable::State::new(),
+        };
+        list.refresh();
+        (list, Command::none())
+    }
+
+    fn title(&self) -> String {
+        "Resources".to_string()
+    }
+
+    fn update(&mut self, message: Message, _clipboard: &mut Clipboard) -> Command<Message> {
+        match message {
+            Message::Refresh => self.refresh(),
+        }
+        Command::none()
+    }
+
+    fn view(&mut self) -> Element<'_, Message> {
+        let mut list = Scrollable::new(&mut self.scroll)
+            .spacing(4)
+            .padding(10)
+            .width(Length::Fill);
+        for row in &self.rows {
+            let swatch = Container::new(Text::new(&row.label))
+                .width(Length::Units(SWATCH_SIZE))
+                .height(Length::Units(SWATCH_SIZE))
+                .center_x()
+                .center_y()
+                .style(Swatch {
+                    fg_color: row.fg_color,
+                    bg_color: row.bg_color,
+                });
+            list = list.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(swatch)
+                    .push(Text::new(&row.name).width(Length::FillPortion(2)))
+                    .push(Text::new(&row.tags).width(Length::FillPortion(3))),
+            );
+        }
+        Container::new(list)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+}
@@ -1,5 +1,10 @@
 #[macro_use]
 extern crate serde;
 
+pub mod game_state;
+#[cfg(feature = "gui")]
+pub mod gui;
 pub mod processing;
 pub mod production;
+#[cfg(test)]
+mod test_util;
@@ -1,6 +1,8 @@
-use production_clicker::production::resource::{ResourceManager, setup_resource_manager};
+use production_clicker::production::resource::{
+    setup_resource_manager, setup_resource_manager_from, ResourceManager,
+};
 use production_clicker::processing::recipe_loader::RecipeLoader;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 fn recipe_path() -> PathBuf {
     let mut start = PathBuf::from("configurations");
@@ -8,10 +10,33 @@ fn recipe_path() -> PathBuf {
     start
 }
 
+fn resource_path() -> PathBuf {
+    let mut start = PathBuf::from("configurations");
+    start.push("resources.json");
+    start
+}
+
 fn main() {
     let mut resource_manager = ResourceManager::new();
-    setup_resource_manager(&mut resource_manager).expect("Couldn't set up resource manager");
+    if let Err(e) = setup_resource_manager_from(&mut resource_manager, resource_path()) {
+        eprintln!("Couldn't load resources from file, using the defaults: {}", e);
+        resource_manager = ResourceManager::new();
+        setup_resource_manager(&mut resource_manager).expect("Couldn't set up resource manager");
+    }
+
+    let mut recipe_loader = RecipeLoader::new(recipe_path(), &resource_manager);
+    match recipe_loader.load_recipes() {
+        Ok(summary) => println!("{}", summary),
+        Err(e) => eprintln!("Couldn't load recipes from file: {}", e),
+    }
+
+    #[cfg(feature = "gui")]
+    {
+        use iced::{Application, Settings};
+        use production_clicker::gui::resource_list::ResourceList;
 
-    let mut recipe_loader = RecipeLoader::new(recipe_path(), &mut resource_manager);
-    recipe_loader.load_recipes().expect("Couldn't load recipes from file");
+        if let Err(e) = ResourceList::run(Settings::with_flags(resource_manager)) {
+            eprintln!("Couldn't open the resource list: {}", e);
+        }
+    }
 }
@@ -0,0 +1,253 @@
+//! Imports a subset of Factorio's prototype data (the `data.raw` dump) into this crate's model.
+//!
+//! Entries of the `item` and `fluid` tables become [`Resource`]s and entries of the `recipe` table
+//! become [`RecipePattern`]s. Only names, icons, ingredients, results and `energy_required` are
+//! mapped; every other field is kept in the `extra` metadata of the created resource or pattern.
+//! Factorio's internal names such as `iron-plate` are converted to display names such as
+//! `Iron Plate`, and the internal name is kept under the `factorio_name` key of `extra`.
+
+use crate::processing::recipe::{RecipePattern, RecipePatternComponent, ResourceSelector};
+use crate::production::resource::{Resource, ResourceManager};
+use iced::Color;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const RESOURCE_TABLES: [&str; 2] = ["item", "fluid"];
+const RESOURCE_FIELDS: [&str; 2] = ["name", "icon"];
+const RECIPE_FIELDS: [&str; 6] = [
+    "name",
+    "ingredients",
+    "result",
+    "result_count",
+    "results",
+    "energy_required",
+];
+/// Factorio's default crafting time, in seconds
+const DEFAULT_ENERGY: f64 = 0.5;
+
+#[derive(Debug)]
+pub struct FactorioImport {
+    pub resources: Vec<Resource>,
+    pub recipes: HashMap<String, RecipePattern>,
+}
+
+/// Converts a Factorio internal name such as `iron-gear-wheel` to `Iron Gear Wheel`
+pub fn display_name(factorio_name: &str) -> String {
+    factorio_name
+        .split('-')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn extra_fields(
+    factorio_name: &str,
+    entry: &Map<String, Value>,
+    known: &[&str],
+) -> HashMap<String, Value> {
+    let mut extra = entry
+        .iter()
+        .filter(|(key, _)| !known.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<HashMap<_, _>>();
+    extra.insert(
+        "factorio_name".to_string(),
+        Value::String(factorio_name.to_string()),
+    );
+    extra
+}
+
+fn entry_name<'a>(key: &'a str, entry: &'a Map<String, Value>) -> &'a str {
+    entry.get("name").and_then(Value::as_str).unwrap_or(key)
+}
+
+fn import_resource(key: &str, entry: &Value) -> Result<Resource, String> {
+    let entry = entry
+        .as_object()
+        .ok_or_else(|| format!("Item {} must be an object", key))?;
+    let name = entry_name(key, entry);
+    let icon = entry.get("icon").and_then(Value::as_str).unwrap_or("");
+    Ok(Resource::new(
+        display_name(name),
+        "",
+        icon,
+        Color::WHITE,
+        Color::BLACK,
+        &[],
+    )
+    .with_extra(extra_fields(name, entry, &RESOURCE_FIELDS)))
+}
+
+/// Reads an ingredient or result, which is either `["name", amount]` or
+/// `{"name": "name", "amount": amount}`
+fn import_component(recipe: &str, component: &Value) -> Result<RecipePatternComponent, String> {
+    let (name, amount) = match component {
+        Value::Array(pair) if pair.len() == 2 => (pair[0].as_str(), pair[1].as_u64()),
+        Value::Object(fields) => (
+            fields.get("name").and_then(Value::as_str),
+            fields.get("amount").and_then(Value::as_u64),
+        ),
+        _ => (None, None),
+    };
+    match (name, amount) {
+        (Some(name), Some(amount)) => Ok(RecipePatternComponent::new(
+            ResourceSelector::Named(display_name(name)),
+            amount as usize,
+        )),
+        _ => Err(format!(
+            "Recipe {} has a malformed component: {}",
+            recipe, component
+        )),
+    }
+}
+
+/// Converts a recipe, returning it along with its display name
+fn import_recipe(key: &str, entry: &Value) -> Result<(String, RecipePattern), String> {
+    let entry = entry
+        .as_object()
+        .ok_or_else(|| format!("Recipe {} must be an object", key))?;
+    let name = entry_name(key, entry);
+
+    let input = entry
+        .get("ingredients")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("Recipe {} has no ingredients", name))?
+        .iter()
+        .map(|ingredient| import_component(name, ingredient))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let output = match (entry.get("results"), entry.get("result")) {
+        (Some(Value::Array(results)), _) => results
+            .iter()
+            .map(|result| import_component(name, result))
+            .collect::<Result<Vec<_>, _>>()?,
+        (None, Some(Value::String(result))) => {
+            let count = entry
+                .get("result_count")
+                .and_then(Value::as_u64)
+                .unwrap_or(1);
+            vec![RecipePatternComponent::new(
+                ResourceSelector::Named(display_name(result)),
+                count as usize,
+            )]
+        }
+        _ => return Err(format!("Recipe {} has no results", name)),
+    };
+
+    let energy = entry
+        .get("energy_required")
+        .and_then(Value::as_f64)
+        .unwrap_or(DEFAULT_ENERGY);
+    let base_time = (energy * 1000.0).round().clamp(0.0, u16::MAX as f64) as u16;
+
+    let pattern = RecipePattern::new(input, output, base_time, vec![]).with_extra(extra_fields(
+        name,
+        entry,
+        &RECIPE_FIELDS,
+    ));
+    Ok((display_name(name), pattern))
+}
+
+/// Converts Factorio prototype data into resources and recipe patterns keyed by display name
+pub fn import(data: &Value) -> Result<FactorioImport, String> {
+    let mut resources = Vec::new();
+    for table in RESOURCE_TABLES.iter() {
+        if let Some(entries) = data.get(*table).and_then(Value::as_object) {
+            for (key, entry) in entries {
+                resources.push(import_resource(key, entry)?);
+            }
+        }
+    }
+
+    let mut recipes = HashMap::new();
+    if let Some(entries) = data.get("recipe").and_then(Value::as_object) {
+        for (key, entry) in entries {
+            let (name, pattern) = import_recipe(key, entry)?;
+            recipes.insert(name, pattern);
+        }
+    }
+
+    Ok(FactorioImport { resources, recipes })
+}
+
+/// Imports Factorio prototype data, adding its resources to the manager and returning its
+/// recipe patterns
+pub fn import_into(
+    data: &Value,
+    manager: &mut ResourceManager,
+) -> Result<HashMap<String, RecipePattern>, String> {
+    let FactorioImport { resources, recipes } = import(data)?;
+    for resource in resources {
+        manager.add_resource(resource).map_err(|e| e.to_string())?;
+    }
+    Ok(recipes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Value {
+        json!({
+            "item": {
+                "iron-plate": { "name": "iron-plate", "type": "item", "icon": "iron-plate.png", "stack_size": 100 },
+                "iron-gear-wheel": { "name": "iron-gear-wheel", "type": "item", "stack_size": 100 }
+            },
+            "recipe": {
+                "iron-gear-wheel": {
+                    "name": "iron-gear-wheel",
+                    "type": "recipe",
+                    "ingredients": [["iron-plate", 2]],
+                    "result": "iron-gear-wheel",
+                    "energy_required": 0.5,
+                    "category": "crafting"
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn imports_resources_and_recipe() {
+        let mut manager = ResourceManager::new();
+        let recipes = import_into(&sample(), &mut manager).unwrap();
+
+        let plate = manager.resource_by_name("Iron Plate").unwrap();
+        assert_eq!(plate.base_icon().to_str(), Some("iron-plate.png"));
+        assert_eq!(plate.extra()["stack_size"], json!(100));
+        assert_eq!(plate.extra()["factorio_name"], json!("iron-plate"));
+        assert!(manager.resource_by_name("Iron Gear Wheel").is_some());
+
+        let gear = &recipes["Iron Gear Wheel"];
+        assert_eq!(gear.base_time(), 500);
+        assert_eq!(
+            gear.input()[0].selector(),
+            &ResourceSelector::Named("Iron Plate".to_string())
+        );
+        assert_eq!(gear.input()[0].quantity(), 2);
+        assert_eq!(
+            gear.output()[0].selector(),
+            &ResourceSelector::Named("Iron Gear Wheel".to_string())
+        );
+        assert_eq!(gear.output()[0].quantity(), 1);
+        assert_eq!(gear.extra()["category"], json!("crafting"));
+        assert!(!gear.extra().contains_key("ingredients"));
+    }
+
+    #[test]
+    fn malformed_ingredient_is_an_error() {
+        let data = json!({
+            "recipe": {
+                "broken": { "ingredients": [["iron-plate"]], "result": "broken" }
+            }
+        });
+        assert!(import(&data).is_err());
+    }
+}
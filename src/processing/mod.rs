@@ -1,2 +1,6 @@
+pub mod production_line;
 pub mod recipe;
+pub mod recipe_graph;
 pub mod recipe_loader;
+#[cfg(feature = "factorio-import")]
+pub mod factorio;
@@ -0,0 +1,388 @@
+use crate::processing::recipe::{Recipe, RecipeComponent};
+use crate::production::inventory::Inventory;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Repeatedly crafts a recipe from its own inventory as time passes. Progress builds up over
+/// ticks, and each time it reaches the recipe's base time the inputs are taken and the outputs
+/// added. If the inputs aren't on hand the line stalls with a full progress bar until they are.
+///
+/// The player can also click a line to push its progress forward by a set amount, after which
+/// further clicks are ignored until the click cooldown has passed in ticks.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProductionLine {
+    recipe: Recipe,
+    inventory: Inventory,
+    progress: Duration,
+    /// How much faster than the recipe's base time the line runs, such as for better buildings
+    #[serde(default = "normal_speed", deserialize_with = "deserialize_speed")]
+    speed_multiplier: f32,
+    click_amount: Duration,
+    click_cooldown: Duration,
+    /// How long until the next click counts
+    cooldown_remaining: Duration,
+}
+
+/// How many clicks it takes to complete a cycle, unless the click amount is changed
+const DEFAULT_CLICKS_PER_CYCLE: u32 = 10;
+/// How long after a click further clicks are ignored, unless changed
+const DEFAULT_CLICK_COOLDOWN: Duration = Duration::from_millis(100);
+
+fn normal_speed() -> f32 {
+    1.0
+}
+
+/// Makes a speed multiplier one a line can run at, from 0 up to the largest finite multiplier.
+/// NaN stops the line.
+fn clamp_speed(speed_multiplier: f32) -> f32 {
+    if speed_multiplier.is_nan() {
+        0.0
+    } else {
+        speed_multiplier.clamp(0.0, f32::MAX)
+    }
+}
+
+/// Reads a speed multiplier, clamped as by [`clamp_speed`], as a save may hold one out of range
+fn deserialize_speed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    f32::deserialize(deserializer).map(clamp_speed)
+}
+
+/// What a click on a [`ProductionLine`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClickResult {
+    /// The click came too soon after the last one and was ignored
+    CoolingDown,
+    /// The craft moved forward without finishing
+    Advanced,
+    /// The craft finished, producing these outputs
+    Crafted(Vec<RecipeComponent>),
+    /// The craft is due but the inputs aren't on hand
+    Stalled,
+}
+
+impl ProductionLine {
+    pub fn new(recipe: Recipe, inventory: Inventory) -> Self {
+        let click_amount =
+            Duration::from_millis(recipe.base_time() as u64) / DEFAULT_CLICKS_PER_CYCLE;
+        ProductionLine {
+            recipe,
+            inventory,
+            progress: Duration::from_millis(0),
+            speed_multiplier: normal_speed(),
+            click_amount,
+            click_cooldown: DEFAULT_CLICK_COOLDOWN,
+            cooldown_remaining: Duration::from_millis(0),
+        }
+    }
+
+    /// Sets how much faster than normal the line runs. Negative multipliers are treated as 0,
+    /// which stops the line. Infinite and NaN multipliers are an error.
+    pub fn with_speed_multiplier(mut self, speed_multiplier: f32) -> Result<Self, String> {
+        if !speed_multiplier.is_finite() {
+            return Err(format!("{} is not a speed multiplier", speed_multiplier));
+        }
+        self.speed_multiplier = clamp_speed(speed_multiplier);
+        Ok(self)
+    }
+
+    /// Sets how far each click advances the line
+    pub fn with_click_amount(mut self, click_amount: Duration) -> Self {
+        self.click_amount = click_amount;
+        self
+    }
+
+    /// Sets how long after a click further clicks are ignored
+    pub fn with_click_cooldown(mut self, click_cooldown: Duration) -> Self {
+        self.click_cooldown = click_cooldown;
+        self
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.speed_multiplier
+    }
+
+    pub fn click_amount(&self) -> Duration {
+        self.click_amount
+    }
+
+    pub fn click_cooldown(&self) -> Duration {
+        self.click_cooldown
+    }
+
+    pub fn recipe(&self) -> &Recipe {
+        &self.recipe
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
+    }
+
+    /// How much progress one craft takes, from the recipe's base time in milliseconds. The line
+    /// gets through it faster or slower depending on its speed multiplier.
+    pub fn cycle_time(&self) -> Duration {
+        Duration::from_millis(self.recipe.base_time() as u64)
+    }
+
+    /// How far through the current craft the line is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        let cycle = self.cycle_time();
+        if cycle == Duration::from_millis(0) {
+            return 0.0;
+        }
+        (self.progress.as_secs_f32() / cycle.as_secs_f32()).min(1.0)
+    }
+
+    /// How many of each output the line is expected to produce a second when it doesn't stall,
+    /// counting outputs that only have a chance of being produced by that chance. A recipe that
+    /// takes no time has an infinite rate, even though [`tick`](Self::tick) crafts it at most
+    /// once a tick.
+    pub fn output_rate(&self) -> HashMap<u64, f32> {
+        self.rate(self.recipe.outputs())
+    }
+
+    /// How many of each input the line consumes a second when it doesn't stall, with the same
+    /// handling of recipes that take no time as [`output_rate`](Self::output_rate)
+    pub fn input_rate(&self) -> HashMap<u64, f32> {
+        self.rate(self.recipe.inputs())
+    }
+
+    fn rate(&self, components: &[RecipeComponent]) -> HashMap<u64, f32> {
+        let crafts_per_second = if self.recipe.base_time() == 0 {
+            f32::INFINITY
+        } else {
+            self.speed_multiplier * 1000.0 / self.recipe.base_time() as f32
+        };
+        let mut rates = HashMap::new();
+        for component in components {
+            let expected = component.quantity() as f32 * component.chance();
+            *rates.entry(component.resource_id()).or_insert(0.0) += expected * crafts_per_second;
+        }
+        rates
+    }
+
+    /// Whether a craft is due but can't be made from what's in the inventory
+    pub fn is_stalled(&self) -> bool {
+        self.progress >= self.cycle_time() && !self.recipe.can_craft(&self.inventory)
+    }
+
+    /// Advances the line by `dt`, scaled by the speed multiplier, crafting every time a cycle
+    /// completes, and returns how many crafts were made. A tick longer than several cycles catches
+    /// up on all of them, stopping at the first craft that can't be made. A recipe that takes no
+    /// time crafts at most once a tick.
+    pub fn tick(&mut self, dt: Duration) -> usize {
+        let cycle = self.cycle_time();
+        self.cooldown_remaining = self
+            .cooldown_remaining
+            .checked_sub(dt)
+            .unwrap_or_else(|| Duration::from_millis(0));
+        // Too much progress to hold saturates, as no craft could take that long anyway
+        let scaled = Duration::try_from_secs_f64(dt.as_secs_f64() * self.speed_multiplier as f64)
+            .unwrap_or(Duration::MAX);
+        self.progress = self.progress.saturating_add(scaled);
+        let mut crafted = 0;
+        while self.progress >= cycle {
+            if self.recipe.craft(&mut self.inventory).is_err() {
+                self.progress = cycle;
+                break;
+            }
+            crafted += 1;
+            self.progress -= cycle;
+            if cycle == Duration::from_millis(0) {
+                break;
+            }
+        }
+        crafted
+    }
+
+    /// Advances the line by the click amount, crafting once if that completes the cycle. Clicks
+    /// during the cooldown are ignored.
+    pub fn click(&mut self) -> ClickResult {
+        self.click_multiplied(|_| 1.0)
+    }
+
+    /// Clicks the line as [`click`](Self::click) does, but a craft makes each output it produces
+    /// `multiplier` times over for that output's resource, rounded to the nearest whole amount.
+    /// Extra outputs that can't be held are dropped.
+    pub fn click_multiplied(&mut self, multiplier: impl Fn(u64) -> f64) -> ClickResult {
+        if self.cooldown_remaining > Duration::from_millis(0) {
+            return ClickResult::CoolingDown;
+        }
+        self.cooldown_remaining = self.click_cooldown;
+        let cycle = self.cycle_time();
+        self.progress = self.progress.saturating_add(self.click_amount);
+        if self.progress < cycle {
+            return ClickResult::Advanced;
+        }
+        match self.recipe.craft(&mut self.inventory) {
+            Ok(produced) => {
+                self.progress = (self.progress - cycle).min(cycle);
+                let produced = produced
+                    .into_iter()
+                    .map(|output| {
+                        let id = output.resource_id();
+                        let boosted = (output.quantity() as f64 * multiplier(id)).round() as usize;
+                        let extra = boosted.saturating_sub(output.quantity());
+                        if extra == 0 || !self.inventory.add(id, extra) {
+                            return output;
+                        }
+                        RecipeComponent::new(id, boosted).with_chance(output.chance())
+                    })
+                    .collect();
+                ClickResult::Crafted(produced)
+            }
+            Err(_) => {
+                self.progress = cycle;
+                ClickResult::Stalled
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::recipe::RecipeComponent;
+
+    /// Smelts 2 ore (0) into 1 ingot (1) every 4 seconds, starting with `ore`
+    fn smelter(ore: usize) -> ProductionLine {
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(0, 2)],
+            vec![RecipeComponent::new(1, 1)],
+            4000,
+            vec![],
+        );
+        let mut inventory = Inventory::new();
+        inventory.set(0, ore);
+        ProductionLine::new(recipe, inventory)
+    }
+
+    #[test]
+    fn full_cycle_crafts_once() {
+        let mut line = smelter(2);
+        assert_eq!(line.tick(Duration::from_millis(1000)), 0);
+        assert_eq!(line.progress(), 0.25);
+        assert_eq!(line.tick(Duration::from_millis(3000)), 1);
+        assert_eq!(line.progress(), 0.0);
+        assert_eq!(line.inventory().quantity(0), 0);
+        assert_eq!(line.inventory().quantity(1), 1);
+    }
+
+    #[test]
+    fn missing_inputs_stall_the_line() {
+        let mut line = smelter(1);
+        assert_eq!(line.tick(Duration::from_millis(5000)), 0);
+        assert!(line.is_stalled());
+        assert_eq!(line.progress(), 1.0);
+        assert_eq!(line.tick(Duration::from_millis(5000)), 0);
+
+        line.inventory_mut().add(0, 1);
+        assert_eq!(line.tick(Duration::from_millis(0)), 1);
+        assert!(!line.is_stalled());
+        assert_eq!(line.progress(), 0.0);
+        assert_eq!(line.inventory().quantity(1), 1);
+    }
+
+    #[test]
+    fn long_tick_catches_up() {
+        let mut line = smelter(6);
+        assert_eq!(line.tick(Duration::from_millis(9000)), 2);
+        assert_eq!(line.progress(), 0.25);
+        assert_eq!(line.inventory().quantity(1), 2);
+
+        assert_eq!(line.tick(Duration::from_millis(20000)), 1);
+        assert!(line.is_stalled());
+        assert_eq!(line.inventory().quantity(0), 0);
+        assert_eq!(line.inventory().quantity(1), 3);
+    }
+
+    #[test]
+    fn clicks_complete_a_craft() {
+        let mut line = smelter(2).with_click_cooldown(Duration::from_millis(0));
+        assert_eq!(line.click_amount(), Duration::from_millis(400));
+        for _ in 0..9 {
+            assert_eq!(line.click(), ClickResult::Advanced);
+        }
+        assert_eq!(
+            line.click(),
+            ClickResult::Crafted(vec![RecipeComponent::new(1, 1)])
+        );
+        assert_eq!(line.inventory().quantity(1), 1);
+        assert_eq!(line.progress(), 0.0);
+
+        let mut line = smelter(0)
+            .with_click_amount(Duration::from_millis(4000))
+            .with_click_cooldown(Duration::from_millis(0));
+        assert_eq!(line.click(), ClickResult::Stalled);
+        assert!(line.is_stalled());
+    }
+
+    #[test]
+    fn clicks_respect_the_cooldown() {
+        let mut line = smelter(2).with_click_cooldown(Duration::from_millis(500));
+        assert_eq!(line.click(), ClickResult::Advanced);
+        assert_eq!(line.click(), ClickResult::CoolingDown);
+        assert_eq!(line.progress(), 0.1);
+
+        line.tick(Duration::from_millis(400));
+        assert_eq!(line.click(), ClickResult::CoolingDown);
+        line.tick(Duration::from_millis(100));
+        assert_eq!(line.click(), ClickResult::Advanced);
+        assert_eq!(line.progress(), 0.325);
+    }
+
+    #[test]
+    fn rates_follow_base_time_and_speed() {
+        let line = smelter(0);
+        let expected = |ore: f32, ingot: f32| {
+            (
+                vec![(0, ore)].into_iter().collect::<HashMap<_, _>>(),
+                vec![(1, ingot)].into_iter().collect::<HashMap<_, _>>(),
+            )
+        };
+        assert_eq!((line.input_rate(), line.output_rate()), expected(0.5, 0.25));
+
+        let mut line = smelter(4).with_speed_multiplier(2.0).unwrap();
+        assert_eq!((line.input_rate(), line.output_rate()), expected(1.0, 0.5));
+        assert_eq!(line.tick(Duration::from_millis(4000)), 2);
+
+        let instant = ProductionLine::new(
+            Recipe::new(vec![], vec![RecipeComponent::new(1, 1)], 0, vec![]),
+            Inventory::new(),
+        );
+        assert_eq!(instant.output_rate()[&1], f32::INFINITY);
+    }
+    #[test]
+    fn extreme_speeds_dont_panic() {
+        assert!(smelter(0).with_speed_multiplier(f32::NAN).is_err());
+        assert!(smelter(0).with_speed_multiplier(f32::INFINITY).is_err());
+
+        let mut stopped = smelter(2).with_speed_multiplier(-3.0).unwrap();
+        assert_eq!(stopped.speed_multiplier(), 0.0);
+        assert_eq!(stopped.tick(Duration::from_millis(4000)), 0);
+        assert_eq!(stopped.progress(), 0.0);
+
+        let mut fastest = smelter(2).with_speed_multiplier(f32::MAX).unwrap();
+        assert_eq!(fastest.tick(Duration::MAX), 1);
+        assert!(fastest.is_stalled());
+        assert_eq!(fastest.tick(Duration::MAX), 0);
+        fastest = fastest
+            .with_click_amount(Duration::MAX)
+            .with_click_cooldown(Duration::from_millis(0));
+        assert_eq!(fastest.click(), ClickResult::Stalled);
+
+        let saved = serde_json::to_string(&smelter(0)).unwrap();
+        for (speed, clamped) in [("-2.5", 0.0), ("1e39", f32::MAX)] {
+            let saved = saved.replace(
+                "\"speed_multiplier\":1.0",
+                &format!("\"speed_multiplier\":{}", speed),
+            );
+            let line: ProductionLine = serde_json::from_str(&saved).unwrap();
+            assert_eq!(line.speed_multiplier(), clamped);
+        }
+    }
+}
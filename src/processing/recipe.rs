@@ -1,13 +1,145 @@
-use crate::production::resource::{ResourceManager, ResourceTag};
-use regex::Regex;
-use std::collections::HashMap;
+use crate::production::inventory::Inventory;
+use crate::production::resource::{Resource, ResourceError, ResourceManager, ResourceTag};
+use rand::Rng;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
 use serde_json::Value;
-use std::ops::Deref;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
-#[derive(Debug, PartialEq, Deserialize)]
+/// Why a recipe pattern couldn't be turned into recipes
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecipeError {
+    /// A component named a resource that doesn't exist
+    UnknownResource(String),
+    /// A tag filter contained something that isn't the name of a tag
+    InvalidTag(Value),
+    /// A component's resource was neither a name nor a tag filter
+    MalformedComponent(Value),
+    /// A tag filter had a key other than `tags`, `mode` or `exclude_tags`
+    UnknownKey(String),
+    /// A requirement wasn't in any of the forms [`Requirement::from_pattern`] reads
+    UnknownRequirement(Value),
+    /// A recipe key was used again by the given file, or by recipes not read from a file if the
+    /// path is empty
+    DuplicateKey(String, PathBuf),
+    /// The recipe source couldn't be read or parsed, for the given reason
+    Unloadable(String),
+    /// A recipe under the key uses a resource id that doesn't exist
+    MissingResource(String, u64),
+    /// A recipe under the key uses none of the resource with the given id
+    ZeroQuantity(String, u64),
+    /// A recipe under the key takes no time to craft
+    ImplausibleTime(String, u16),
+    /// A recipe under the key has a requirement that can never be met
+    UnsatisfiableRequirement(String, Requirement),
+    /// An error found by checking the loaded recipes together, with its message
+    Conflict(String),
+}
+
+impl Display for RecipeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecipeError::UnknownResource(resource) => write!(f, "No resource {} exists", resource),
+            RecipeError::InvalidTag(tag) => write!(f, "{} is not a resource tag", tag),
+            RecipeError::MalformedComponent(component) => {
+                write!(f, "{} is not a resource name or tag filter", component)
+            }
+            RecipeError::UnknownKey(key) => write!(f, "{} is not a tag filter key", key),
+            RecipeError::UnknownRequirement(requirement) => {
+                write!(f, "{} is not a recipe requirement", requirement)
+            }
+            RecipeError::DuplicateKey(key, file) if file.as_os_str().is_empty() => {
+                write!(f, "Recipe {} is already loaded", key)
+            }
+            RecipeError::DuplicateKey(key, file) => {
+                write!(f, "Recipe {} is defined again in {}", key, file.display())
+            }
+            RecipeError::Unloadable(reason) => write!(f, "Couldn't load recipes: {}", reason),
+            RecipeError::MissingResource(key, id) => {
+                write!(
+                    f,
+                    "Recipe {} uses resource #{}, which doesn't exist",
+                    key, id
+                )
+            }
+            RecipeError::ZeroQuantity(key, id) => {
+                write!(f, "Recipe {} uses none of resource #{}", key, id)
+            }
+            RecipeError::ImplausibleTime(key, time) => {
+                write!(f, "Recipe {} has a base time of {}", key, time)
+            }
+            RecipeError::UnsatisfiableRequirement(key, requirement) => {
+                write!(
+                    f,
+                    "Recipe {} requires {:?}, which can't be met",
+                    key, requirement
+                )
+            }
+            RecipeError::Conflict(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for RecipeError {}
+
+impl From<ResourceError> for RecipeError {
+    fn from(error: ResourceError) -> Self {
+        match error {
+            ResourceError::NotFound(resource) => RecipeError::UnknownResource(resource),
+            other => RecipeError::Conflict(other.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecipeComponent {
     resource_id: u64,
     quantity: usize,
+    /// The chance, from 0 to 1, that an output is produced by a craft. Inputs are always consumed.
+    #[serde(
+        default = "certain",
+        skip_serializing_if = "is_certain",
+        deserialize_with = "deserialize_chance"
+    )]
+    chance: f32,
+}
+
+/// Components are equal when their resource, quantity and chance are equal. Chances are always
+/// clamped between 0 and 1, so comparing their bits is the same as comparing their values.
+impl PartialEq for RecipeComponent {
+    fn eq(&self, other: &Self) -> bool {
+        self.resource_id == other.resource_id
+            && self.quantity == other.quantity
+            && self.chance.to_bits() == other.chance.to_bits()
+    }
+}
+
+impl Eq for RecipeComponent {}
+
+fn certain() -> f32 {
+    1.0
+}
+
+fn is_certain(chance: &f32) -> bool {
+    *chance == 1.0
+}
+
+/// Clamps a chance between 0 and 1, with NaN and -0 becoming 0
+fn clamp_chance(chance: f32) -> f32 {
+    if chance.is_nan() || chance <= 0.0 {
+        0.0
+    } else {
+        chance.min(1.0)
+    }
+}
+
+/// Reads a chance, clamped as by [`clamp_chance`]
+fn deserialize_chance<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    f32::deserialize(deserializer).map(clamp_chance)
 }
 
 impl RecipeComponent {
@@ -15,97 +147,1605 @@ impl RecipeComponent {
         RecipeComponent {
             resource_id,
             quantity,
+            chance: 1.0,
         }
     }
+
+    /// Sets the chance an output is produced, clamped between 0 and 1
+    pub fn with_chance(mut self, chance: f32) -> Self {
+        self.chance = clamp_chance(chance);
+        self
+    }
+
+    pub fn resource_id(&self) -> u64 {
+        self.resource_id
+    }
+
+    pub fn quantity(&self) -> usize {
+        self.quantity
+    }
+
+    pub fn chance(&self) -> f32 {
+        self.chance
+    }
 }
 
-#[derive(Debug)]
+/// Why a recipe couldn't be crafted
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CraftError {
+    /// Less of an input was on hand than the recipe needs
+    Insufficient {
+        resource_id: u64,
+        required: usize,
+        available: usize,
+    },
+    /// Adding the outputs would overflow how much of the resource can be held
+    Overflow(u64),
+}
+
+impl Display for CraftError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CraftError::Insufficient {
+                resource_id,
+                required,
+                available,
+            } => write!(
+                f,
+                "Needed {} of resource #{} but only {} are available",
+                required, resource_id, available
+            ),
+            CraftError::Overflow(resource_id) => {
+                write!(f, "Too much of resource #{} to hold", resource_id)
+            }
+        }
+    }
+}
+
+impl Error for CraftError {}
+
+/// Something that must hold before a recipe can be crafted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Requirement {
+    /// A building of the given kind must be available
+    Building(String),
+    /// The named technology must have been researched
+    TechUnlocked(String),
+    /// The player must be at least this level
+    MinLevel(u32),
+}
+
+impl Requirement {
+    /// Whether the requirement can ever be met. Buildings and technologies must be named.
+    pub fn is_satisfiable(&self) -> bool {
+        match self {
+            Requirement::Building(name) | Requirement::TechUnlocked(name) => !name.is_empty(),
+            Requirement::MinLevel(_) => true,
+        }
+    }
+
+    /// Reads a requirement from a recipe pattern. Strings have the form `"Kind: argument"`, such as
+    /// `"Building: Furnace"`, while objects are requirements as serialized, such as
+    /// `{"MinLevel": 5}`.
+    pub fn from_pattern(value: &Value) -> Result<Self, RecipeError> {
+        let unknown = || RecipeError::UnknownRequirement(value.clone());
+        match value {
+            Value::String(requirement) => {
+                let mut parts = requirement.splitn(2, ':').map(str::trim);
+                let kind = parts.next().unwrap_or_default();
+                let argument = parts.next().ok_or_else(unknown)?;
+                match kind {
+                    "Building" => Ok(Requirement::Building(argument.to_string())),
+                    "TechUnlocked" => Ok(Requirement::TechUnlocked(argument.to_string())),
+                    "MinLevel" => argument
+                        .parse()
+                        .map(Requirement::MinLevel)
+                        .map_err(|_| unknown()),
+                    _ => Err(unknown()),
+                }
+            }
+            Value::Object(_) => serde_json::from_value(value.clone()).map_err(|_| unknown()),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Recipe {
     inputs: Vec<RecipeComponent>,
     outputs: Vec<RecipeComponent>,
+    /// Resources that must be on hand to craft, but aren't consumed, such as tools
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    catalysts: Vec<RecipeComponent>,
+    /// Input slots which consume any one of their alternatives, such as 2 coal or 1 charcoal
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    any_of: Vec<Vec<RecipeComponent>>,
     base_time: u16,
-    requirements: Vec<()>
+    /// Time paid once per batch of crafts, on top of the per-unit `base_time`
+    setup_time: u16,
+    /// Whether this recipe is the preferred way to produce its outputs
+    primary: bool,
+    requirements: Vec<Requirement>,
 }
 
 impl Recipe {
-    pub fn new(inputs: Vec<RecipeComponent>, outputs: Vec<RecipeComponent>, base_time: u16, requirements: Vec<()>) -> Self {
-        Recipe { inputs, outputs, base_time, requirements }
+    pub fn new(inputs: Vec<RecipeComponent>, outputs: Vec<RecipeComponent>, base_time: u16, requirements: Vec<Requirement>) -> Self {
+        Recipe { inputs, outputs, catalysts: vec![], any_of: vec![], base_time, setup_time: 0, primary: false, requirements }
+    }
+
+    pub fn with_catalysts(mut self, catalysts: Vec<RecipeComponent>) -> Self {
+        self.catalysts = catalysts;
+        self
+    }
+
+    pub fn with_any_of(mut self, any_of: Vec<Vec<RecipeComponent>>) -> Self {
+        self.any_of = any_of;
+        self
+    }
+
+    pub fn with_setup_time(mut self, setup_time: u16) -> Self {
+        self.setup_time = setup_time;
+        self
+    }
+
+    pub fn with_primary(mut self, primary: bool) -> Self {
+        self.primary = primary;
+        self
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+
+    pub fn inputs(&self) -> &Vec<RecipeComponent> {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &Vec<RecipeComponent> {
+        &self.outputs
+    }
+
+    pub fn catalysts(&self) -> &Vec<RecipeComponent> {
+        &self.catalysts
+    }
+
+    /// The input slots which each consume one of their alternatives. See
+    /// [`inputs_for`](Self::inputs_for) for which alternative is picked.
+    pub fn any_of(&self) -> &Vec<Vec<RecipeComponent>> {
+        &self.any_of
+    }
+
+    /// The input slots a craft consumes from, each consuming one of its alternatives: every
+    /// fixed input as a slot of its own, followed by the [`any_of`](Self::any_of) slots
+    pub fn input_slots(&self) -> impl Iterator<Item = &[RecipeComponent]> + '_ {
+        self.inputs
+            .iter()
+            .map(std::slice::from_ref)
+            .chain(self.any_of.iter().map(Vec::as_slice))
+    }
+
+    /// Whether the recipe could be crafted given the resources `available` says can be had,
+    /// regardless of quantity: whether every [input slot](Self::input_slots) has an available
+    /// alternative and every catalyst is available
+    pub fn craftable_from(&self, available: impl Fn(u64) -> bool) -> bool {
+        self.input_slots().all(|slot| {
+            slot.iter()
+                .any(|component| available(component.resource_id))
+        }) && self
+            .catalysts
+            .iter()
+            .all(|catalyst| available(catalyst.resource_id))
     }
+
+    /// Whether this recipe consumes, could consume, produces or needs as a catalyst the given
+    /// resource
+    pub fn references(&self, resource_id: u64) -> bool {
+        self.inputs
+            .iter()
+            .chain(self.any_of.iter().flatten())
+            .chain(self.outputs.iter())
+            .chain(self.catalysts.iter())
+            .any(|component| component.resource_id == resource_id)
+    }
+
+    pub fn requirements(&self) -> &Vec<Requirement> {
+        &self.requirements
+    }
+
+    pub fn base_time(&self) -> u16 {
+        self.base_time
+    }
+
+    pub fn set_base_time(&mut self, base_time: u16) {
+        self.base_time = base_time;
+    }
+
+    pub fn setup_time(&self) -> u16 {
+        self.setup_time
+    }
+
+    /// Sums the quantities of components by resource, as a resource may be listed more than once
+    fn totals(components: &[RecipeComponent]) -> HashMap<u64, usize> {
+        let mut totals = HashMap::new();
+        for component in components {
+            *totals.entry(component.resource_id).or_insert(0) += component.quantity;
+        }
+        totals
+    }
+
+    /// How much of each resource must be on hand to craft with the given inputs: the inputs, plus
+    /// the catalysts on top of any of the same resource consumed
+    fn required(&self, inputs: &[RecipeComponent]) -> HashMap<u64, (usize, usize)> {
+        let mut required = HashMap::new();
+        for (id, consumed) in Self::totals(inputs) {
+            required.entry(id).or_insert((0, 0)).0 = consumed;
+        }
+        for (id, kept) in Self::totals(&self.catalysts) {
+            required.entry(id).or_insert((0, 0)).1 = kept;
+        }
+        required
+    }
+
+    /// Whether the inventory holds enough of every input and catalyst for the given inputs
+    fn has_required(&self, inputs: &[RecipeComponent], inventory: &Inventory) -> bool {
+        self.required(inputs)
+            .into_iter()
+            .all(|(id, (consumed, kept))| inventory.quantity(id) >= consumed + kept)
+    }
+
+    /// The inputs crafting from the inventory would consume: the fixed inputs, plus one
+    /// alternative from each [`any_of`](Self::any_of) slot. Slots are filled in the order they're
+    /// listed, each with its first alternative the inventory holds enough of alongside the fixed
+    /// inputs, the catalysts and the alternatives already picked. Earlier slots aren't revisited,
+    /// so an earlier pick can leave a later slot short. A slot with no such alternative gets its
+    /// first one, which the craft then fails for want of.
+    pub fn inputs_for(&self, inventory: &Inventory) -> Vec<RecipeComponent> {
+        let mut inputs = self.inputs.clone();
+        for slot in &self.any_of {
+            let picked = slot
+                .iter()
+                .find(|alternative| {
+                    let mut candidate = inputs.clone();
+                    candidate.push((*alternative).clone());
+                    self.has_required(&candidate, inventory)
+                })
+                .or_else(|| slot.first());
+            inputs.extend(picked.cloned());
+        }
+        inputs
+    }
+
+    /// Whether the inventory holds enough of every input and catalyst, picking alternatives for
+    /// the `any_of` slots as [`inputs_for`](Self::inputs_for) does
+    pub fn can_craft(&self, inventory: &Inventory) -> bool {
+        self.has_required(&self.inputs_for(inventory), inventory)
+    }
+
+    /// Takes the inputs from the inventory and adds the outputs to it, rolling for outputs that
+    /// only have a chance of being produced. See [`craft_with_rng`](Self::craft_with_rng).
+    pub fn craft(&self, inventory: &mut Inventory) -> Result<Vec<RecipeComponent>, CraftError> {
+        self.craft_with_rng(inventory, &mut rand::thread_rng())
+    }
+
+    /// Takes the inputs from the inventory and adds the outputs to it, returning the outputs
+    /// produced. Each output with less than a certain chance is rolled for with `rng`. The inputs
+    /// are taken before the outputs are added, so a resource that is both consumed and produced
+    /// must be on hand. Catalysts must be on hand but are left in the inventory. The `any_of` slots
+    /// are filled as [`inputs_for`](Self::inputs_for) describes. On failure the inventory is left
+    /// unchanged.
+    pub fn craft_with_rng<R: Rng + ?Sized>(
+        &self,
+        inventory: &mut Inventory,
+        rng: &mut R,
+    ) -> Result<Vec<RecipeComponent>, CraftError> {
+        let mut after = HashMap::new();
+        for (id, (consumed, kept)) in self.required(&self.inputs_for(inventory)) {
+            let available = inventory.quantity(id);
+            if available < consumed + kept {
+                return Err(CraftError::Insufficient {
+                    resource_id: id,
+                    required: consumed + kept,
+                    available,
+                });
+            }
+            after.insert(id, available - consumed);
+        }
+        let produced_outputs = self
+            .outputs
+            .iter()
+            .filter(|output| output.chance >= 1.0 || rng.gen_bool(output.chance as f64))
+            .cloned()
+            .collect::<Vec<_>>();
+        for (id, produced) in Self::totals(&produced_outputs) {
+            let current = after
+                .get(&id)
+                .copied()
+                .unwrap_or_else(|| inventory.quantity(id));
+            let total = current
+                .checked_add(produced)
+                .ok_or(CraftError::Overflow(id))?;
+            after.insert(id, total);
+        }
+        for (id, quantity) in after {
+            inventory.set(id, quantity);
+        }
+        Ok(produced_outputs)
+    }
+
+    /// How much more the outputs are worth than the inputs, by the base values of their resources.
+    /// Outputs count for as much as they're expected to be worth, by their chance of being
+    /// produced, and each `any_of` slot costs as much as its cheapest alternative. A recipe that
+    /// loses value has a negative margin.
+    pub fn profit_margin(&self, manager: &ResourceManager) -> Result<f64, ResourceError> {
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| {
+                manager.resource_by_id_res(output.resource_id).map(|resource| {
+                    resource.base_value() * output.quantity as f64 * output.chance as f64
+                })
+            })
+            .sum::<Result<f64, _>>()?;
+        let inputs =
+            self.input_cost(|id| manager.resource_by_id_res(id).map(Resource::base_value))?;
+        Ok(outputs - inputs)
+    }
+
+    /// What the inputs consumed are worth, given what one of each resource is worth. Each
+    /// [input slot](Self::input_slots) costs as much as its cheapest alternative, and an empty
+    /// slot costs nothing.
+    pub(crate) fn input_cost(
+        &self,
+        value: impl Fn(u64) -> Result<f64, ResourceError>,
+    ) -> Result<f64, ResourceError> {
+        let mut cost = 0.0;
+        for slot in self.input_slots().filter(|slot| !slot.is_empty()) {
+            let mut cheapest = f64::INFINITY;
+            for component in slot {
+                cheapest = cheapest.min(value(component.resource_id)? * component.quantity as f64);
+            }
+            cost += cheapest;
+        }
+        Ok(cost)
+    }
+
+    /// The value of the outputs less the value of the inputs consumed, as with
+    /// [`profit_margin`](Self::profit_margin). Catalysts aren't consumed, so their value doesn't
+    /// count. A recipe using a resource the manager doesn't have makes no profit.
+    pub fn profit(&self, manager: &ResourceManager) -> f64 {
+        self.profit_margin(manager).unwrap_or(0.0)
+    }
+
+    /// The total time to craft `units` of this recipe in one batch. The setup time is paid once for
+    /// the whole batch, while the base time is paid for every unit. An empty batch takes no time.
+    pub fn batch_time(&self, units: u32) -> u64 {
+        if units == 0 {
+            return 0;
+        }
+        self.setup_time as u64 + self.base_time as u64 * units as u64
+    }
+}
+
+/// How a tag filter combines its tags
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TagMatch {
+    /// Resources with every tag match
+    All,
+    /// Resources with any of the tags match
+    Any,
 }
 
-#[derive(Debug, Deserialize)]
+/// The keys a tag filter may have
+const FILTER_KEYS: [&str; 3] = ["tags", "mode", "exclude_tags"];
+
+/// Which resources a pattern component stands for. In a recipe file this is either a resource name
+/// or a tag filter object such as `{"tags": ["Metal"], "exclude_tags": ["Ore"], "mode": "all"}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceSelector {
+    /// The single resource with this name, or a name template such as `"{input0} Ingot"` in an
+    /// output
+    Named(String),
+    /// Every resource with the `tags`, as combined by `mode`, and none of the `exclude` tags
+    ByTags {
+        tags: Vec<ResourceTag>,
+        exclude: Vec<ResourceTag>,
+        mode: TagMatch,
+    },
+}
+
+impl ResourceSelector {
+    /// Reads a selector as written in a recipe file
+    pub fn from_value(value: &Value) -> Result<Self, RecipeError> {
+        match value {
+            Value::String(name) => Ok(ResourceSelector::Named(name.clone())),
+            Value::Object(dict) => {
+                if let Some(key) = dict.keys().find(|key| !FILTER_KEYS.contains(&key.as_str())) {
+                    return Err(RecipeError::UnknownKey(key.clone()));
+                }
+                let mode = match dict.get("mode").map(Value::as_str) {
+                    None | Some(Some("all")) => TagMatch::All,
+                    Some(Some("any")) => TagMatch::Any,
+                    Some(_) => return Err(RecipeError::MalformedComponent(value.clone())),
+                };
+                Ok(ResourceSelector::ByTags {
+                    tags: tags_under(value, dict.get("tags"))?,
+                    exclude: tags_under(value, dict.get("exclude_tags"))?,
+                    mode,
+                })
+            }
+            _ => Err(RecipeError::MalformedComponent(value.clone())),
+        }
+    }
+
+    /// Writes the selector as it would appear in a recipe file, leaving out default filter keys
+    pub fn to_value(&self) -> Value {
+        match self {
+            ResourceSelector::Named(name) => Value::String(name.clone()),
+            ResourceSelector::ByTags {
+                tags,
+                exclude,
+                mode,
+            } => {
+                let names = |tags: &[ResourceTag]| {
+                    tags.iter()
+                        .map(|tag| Value::String(tag.to_string()))
+                        .collect::<Vec<_>>()
+                };
+                let mut dict = serde_json::Map::new();
+                dict.insert("tags".to_string(), Value::Array(names(tags)));
+                if !exclude.is_empty() {
+                    dict.insert("exclude_tags".to_string(), Value::Array(names(exclude)));
+                }
+                if *mode == TagMatch::Any {
+                    dict.insert("mode".to_string(), Value::from("any"));
+                }
+                Value::Object(dict)
+            }
+        }
+    }
+}
+
+impl Serialize for ResourceSelector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ResourceSelector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        ResourceSelector::from_value(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parses a list of tags in a tag filter, which is empty if the key isn't present
+fn tags_under(filter: &Value, tags: Option<&Value>) -> Result<Vec<ResourceTag>, RecipeError> {
+    let tags = match tags {
+        Some(Value::Array(tags)) => tags,
+        Some(_) => return Err(RecipeError::MalformedComponent(filter.clone())),
+        None => return Ok(vec![]),
+    };
+    tags.iter()
+        .map(|tag| {
+            tag.as_str()
+                .and_then(|name| name.parse::<ResourceTag>().ok())
+                .ok_or_else(|| RecipeError::InvalidTag(tag.clone()))
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RecipePatternComponent {
-    resource: Value,
-    quantity: usize
+    resource: ResourceSelector,
+    quantity: usize,
+    #[serde(
+        default = "certain",
+        skip_serializing_if = "is_certain",
+        deserialize_with = "deserialize_chance"
+    )]
+    chance: f32,
 }
 
 impl RecipePatternComponent {
+    pub fn new(resource: ResourceSelector, quantity: usize) -> Self {
+        RecipePatternComponent {
+            resource,
+            quantity,
+            chance: certain(),
+        }
+    }
+
+    /// Sets the chance an output is produced, clamped between 0 and 1
+    pub fn with_chance(mut self, chance: f32) -> Self {
+        self.chance = clamp_chance(chance);
+        self
+    }
 
-    pub fn pattern(&self) -> &Value {
+    /// The recipe component this stands for once its resource is picked
+    fn resolve(&self, resource_id: u64) -> RecipeComponent {
+        RecipeComponent::new(resource_id, self.quantity).with_chance(self.chance)
+    }
+
+    pub fn selector(&self) -> &ResourceSelector {
         &self.resource
     }
 
     pub fn quantity(&self) -> usize {
         self.quantity
     }
+
+    /// Every resource this component could stand for, ordered by id
+    fn candidates<'m>(
+        &self,
+        manager: &'m ResourceManager,
+    ) -> Result<Vec<&'m Resource>, RecipeError> {
+        match &self.resource {
+            ResourceSelector::Named(name) => Ok(vec![manager.resource_by_name_res(name)?]),
+            ResourceSelector::ByTags {
+                tags,
+                exclude,
+                mode,
+            } => {
+                let mut resources = match mode {
+                    TagMatch::All => manager.resources_with_tags(tags.iter()),
+                    TagMatch::Any => manager.resources_with_any_tag(tags.iter()),
+                };
+                resources.retain(|res| !exclude.iter().any(|tag| res.contains_tag(tag)));
+                Ok(resources)
+            }
+        }
+    }
+
+    /// The name template of a component that names a resource after the chosen inputs, such as
+    /// `"{input0} Ingot"`
+    fn template(&self) -> Option<&str> {
+        match &self.resource {
+            ResourceSelector::Named(name) if input_reference().is_match(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+fn is_zero(value: &u16) -> bool {
+    *value == 0
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RecipePattern {
     input: Vec<RecipePatternComponent>,
     output: Vec<RecipePatternComponent>,
+    /// Must name their resources, as catalysts don't take part in expanding a pattern
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    catalysts: Vec<RecipePatternComponent>,
+    /// Input slots taking any one of their alternatives. Like catalysts, they must name their
+    /// resources.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    any_of: Vec<Vec<RecipePatternComponent>>,
     base_time: u16,
-    requirements: Vec<String>
+    #[serde(default, skip_serializing_if = "is_zero")]
+    setup_time: u16,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    primary: bool,
+    /// Requirements as written in the recipe file, read by [`Requirement::from_pattern`]
+    requirements: Vec<Value>,
+    /// Metadata this crate doesn't model, such as fields kept from imported data
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    extra: HashMap<String, Value>,
 }
 
 impl RecipePattern {
+    pub fn new(
+        input: Vec<RecipePatternComponent>,
+        output: Vec<RecipePatternComponent>,
+        base_time: u16,
+        requirements: Vec<Value>,
+    ) -> Self {
+        RecipePattern {
+            input,
+            output,
+            catalysts: vec![],
+            any_of: vec![],
+            base_time,
+            setup_time: 0,
+            primary: false,
+            requirements,
+            extra: HashMap::new(),
+        }
+    }
+
+    pub fn with_extra(mut self, extra: HashMap<String, Value>) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    pub fn with_catalysts(mut self, catalysts: Vec<RecipePatternComponent>) -> Self {
+        self.catalysts = catalysts;
+        self
+    }
+
+    pub fn with_any_of(mut self, any_of: Vec<Vec<RecipePatternComponent>>) -> Self {
+        self.any_of = any_of;
+        self
+    }
+
+    pub fn input(&self) -> &Vec<RecipePatternComponent> {
+        &self.input
+    }
+
+    pub fn output(&self) -> &Vec<RecipePatternComponent> {
+        &self.output
+    }
+
+    pub fn base_time(&self) -> u16 {
+        self.base_time
+    }
+
+    pub fn extra(&self) -> &HashMap<String, Value> {
+        &self.extra
+    }
+
+    fn resolved_any_of(
+        &self,
+        manager: &ResourceManager,
+    ) -> Result<Vec<Vec<RecipeComponent>>, RecipeError> {
+        self.any_of
+            .iter()
+            .map(|slot| resolve_named(slot, manager))
+            .collect()
+    }
 
-    pub fn into_recipes(self, manager: &ResourceManager) -> Vec<Recipe> {
-        println!("{:?}", self);
-        let inputs_raw = self.input
+    fn parsed_requirements(&self) -> Result<Vec<Requirement>, RecipeError> {
+        self.requirements
+            .iter()
+            .map(Requirement::from_pattern)
+            .collect()
+    }
+
+    /// Whether every component names a single resource, so the pattern describes exactly one recipe
+    fn is_fully_named(&self) -> bool {
+        self.input
+            .iter()
+            .chain(self.output.iter())
+            .all(|component| matches!(component.resource, ResourceSelector::Named(_)))
+            && self
+                .output
+                .iter()
+                .all(|component| component.template().is_none())
+    }
+
+    /// Builds the only recipe of a pattern whose components all name a single resource
+    fn into_single_recipe(self, manager: &ResourceManager) -> Result<Recipe, RecipeError> {
+        let requirements = self.parsed_requirements()?;
+        let inputs = resolve_named(&self.input, manager)?;
+        let outputs = resolve_named(&self.output, manager)?;
+        Ok(Recipe::new(inputs, outputs, self.base_time, requirements)
+            .with_catalysts(resolve_named(&self.catalysts, manager)?)
+            .with_any_of(self.resolved_any_of(manager)?)
+            .with_setup_time(self.setup_time)
+            .with_primary(self.primary))
+    }
+
+    pub fn into_recipes(self, manager: &ResourceManager) -> Result<Vec<Recipe>, RecipeError> {
+        if self.is_fully_named() {
+            return Ok(vec![self.into_single_recipe(manager)?]);
+        }
+        let requirements = self.parsed_requirements()?;
+        let catalysts = resolve_named(&self.catalysts, manager)?;
+        let any_of = self.resolved_any_of(manager)?;
+        let input_count = self.input.len();
+        // Templated outputs depend on the chosen inputs, so only the other outputs get slots
+        let slots = self
+            .input
             .iter()
-            .map(|RecipePatternComponent{resource, quantity}| {
-                match resource {
-                    Value::String(name) => {
-                        vec![manager.resource_by_name(name)
-                            .expect(format!("No resource with name {}", name).as_str())]
-                    }
-                    Value::Object(dict) => {
-                        let mut resources = manager.resources().collect::<Vec<_>>();
-                        if dict.contains_key("tags") {
-                            if let Value::Array(tags) = &dict["tags"] {
-                                let tags = tags.iter()
-                                    .map(|tag| {
-                                        let tag_string = tag.as_str().expect("Tag must string");
-                                        let tag: ResourceTag = serde_json::from_str(format!("\"{}\"", tag_string).as_str()).unwrap();
-                                        tag
-                                    })
-                                    .collect::<Vec<_>>();
-                                resources =
-                                    resources.into_iter().filter(
-                                        |res| res.contains_all_tags(tags.iter())
-                                    )
-                                        .collect();
-                            } else {
-                                panic!("Invalid entry for tags")
-                            }
+            .chain(
+                self.output
+                    .iter()
+                    .filter(|component| component.template().is_none()),
+            )
+            .map(|component| component.candidates(manager))
+            .collect::<Result<Vec<_>, RecipeError>>()?;
+
+        // Slots sharing a filter yield the same resources in different orders, which are one recipe
+        let key = |components: &[RecipeComponent]| {
+            let mut key = components
+                .iter()
+                .map(|component| (component.resource_id, component.quantity))
+                .collect::<Vec<_>>();
+            key.sort_unstable();
+            key
+        };
+        let mut seen = HashSet::new();
+        let mut recipes = vec![];
+        for combination in super_set_iterator(slots) {
+            let (inputs, fixed_outputs) = combination.split_at(input_count);
+            let mut fixed_outputs = fixed_outputs.iter();
+            let outputs = self
+                .output
+                .iter()
+                .map(|component| {
+                    let resource = match component.template() {
+                        Some(template) => {
+                            manager.resource_by_name_res(fill_template(template, inputs)?)?
                         }
-                        resources
-                    }
-                    _ => panic!("Invalid resource")
-                }
-            })
-            .collect::<Vec<_>>();
+                        None => fixed_outputs.next().unwrap(),
+                    };
+                    Ok(component.resolve(resource.id()))
+                })
+                .collect::<Result<Vec<_>, RecipeError>>()?;
+            let components = self
+                .input
+                .iter()
+                .zip(inputs)
+                .map(|(component, resource)| component.resolve(resource.id()))
+                .collect::<Vec<_>>();
+            let recipe = Recipe::new(components, outputs, self.base_time, requirements.clone())
+                .with_catalysts(catalysts.clone())
+                .with_any_of(any_of.clone())
+                .with_setup_time(self.setup_time)
+                .with_primary(self.primary);
+            if seen.insert((key(&recipe.inputs), key(&recipe.outputs))) {
+                recipes.push(recipe);
+            }
+        }
+        Ok(recipes)
+    }
+}
 
-        println!("{:?}", inputs);
+/// Looks up the resources of components that each name a single resource
+fn resolve_named(
+    components: &[RecipePatternComponent],
+    manager: &ResourceManager,
+) -> Result<Vec<RecipeComponent>, RecipeError> {
+    components
+        .iter()
+        .map(|component| match &component.resource {
+            ResourceSelector::Named(name) => {
+                let id = manager.resource_by_name_res(name)?.id();
+                Ok(component.resolve(id))
+            }
+            selector => Err(RecipeError::MalformedComponent(selector.to_value())),
+        })
+        .collect()
+}
+
+/// Matches a reference to an input slot in an output name template, such as `{input0}`. The regex
+/// is compiled the first time it's needed.
+fn input_reference() -> &'static Regex {
+    static INPUT_REFERENCE: OnceLock<Regex> = OnceLock::new();
+    INPUT_REFERENCE.get_or_init(|| Regex::new(r"\{input(\d+)\}").unwrap())
+}
 
-        todo!()
+/// Substitutes the names of the chosen input resources into an output name template
+fn fill_template(template: &str, inputs: &[&Resource]) -> Result<String, RecipeError> {
+    let reference = input_reference();
+    for captures in reference.captures_iter(template) {
+        match captures[1].parse::<usize>() {
+            Ok(slot) if slot < inputs.len() => {}
+            _ => return Err(RecipeError::MalformedComponent(Value::from(template))),
+        }
     }
+    let filled = reference.replace_all(template, |captures: &Captures| {
+        inputs[captures[1].parse::<usize>().unwrap()].name().clone()
+    });
+    Ok(filled.into_owned())
 }
 
-fn super_set_iterator<I, T>(input: I) -> Vec<Vec<T>> {
-    
+/// Gets the cartesian product of the groups, every combination picking one item from each group in
+/// order. Earlier groups vary slowest. If any group is empty there are no combinations, while no
+/// groups at all gives a single empty combination.
+fn super_set_iterator<I, T>(input: I) -> Vec<Vec<T>>
+where
+    I: IntoIterator<Item = Vec<T>>,
+    T: Clone,
+{
+    let mut combinations = vec![vec![]];
+    for group in input {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                group.iter().map(move |item| {
+                    let mut extended = combination.clone();
+                    extended.push(item.clone());
+                    extended
+                })
+            })
+            .collect();
+    }
+    combinations
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::production::resource::setup_resource_manager;
+    use iced::Color;
 
+    #[test]
+    fn super_set_picks_one_from_each_group() {
+        let product = super_set_iterator(vec![vec!["Iron", "Copper"], vec!["Coal"]]);
+        assert_eq!(product, vec![vec!["Iron", "Coal"], vec!["Copper", "Coal"]]);
+
+        let product = super_set_iterator(vec![vec![1, 2], vec![3, 4]]);
+        assert_eq!(
+            product,
+            vec![vec![1, 3], vec![1, 4], vec![2, 3], vec![2, 4]]
+        );
+    }
+
+    #[test]
+    fn super_set_edge_cases() {
+        assert_eq!(
+            super_set_iterator(vec![vec!["Iron", "Copper"]]),
+            vec![vec!["Iron"], vec!["Copper"]]
+        );
+        assert!(super_set_iterator(vec![vec!["Iron"], vec![]]).is_empty());
+        assert_eq!(
+            super_set_iterator(Vec::<Vec<&str>>::new()),
+            vec![Vec::<&str>::new()]
+        );
+    }
+
+    #[test]
+    fn batch_pays_setup_time_once() {
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(0, 1)],
+            vec![RecipeComponent::new(1, 1)],
+            4000,
+            vec![],
+        )
+        .with_setup_time(1500);
+        assert_eq!(recipe.batch_time(1), 5500);
+        assert_eq!(recipe.batch_time(10), 1500 + 10 * 4000);
+        assert_eq!(recipe.batch_time(0), 0);
+    }
+
+    #[test]
+    fn craft_needs_exact_inputs() {
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(0, 2), RecipeComponent::new(1, 1)],
+            vec![RecipeComponent::new(2, 1)],
+            4000,
+            vec![],
+        );
+        let mut inventory = Inventory::new();
+        inventory.set(0, 2);
+        inventory.set(1, 1);
+        assert!(recipe.can_craft(&inventory));
+        recipe.craft(&mut inventory).unwrap();
+        assert_eq!(inventory.quantity(0), 0);
+        assert_eq!(inventory.quantity(1), 0);
+        assert_eq!(inventory.quantity(2), 1);
+
+        inventory.set(0, 1);
+        inventory.set(1, 5);
+        let before = inventory.clone();
+        assert!(!recipe.can_craft(&inventory));
+        assert_eq!(
+            recipe.craft(&mut inventory),
+            Err(CraftError::Insufficient {
+                resource_id: 0,
+                required: 2,
+                available: 1
+            })
+        );
+        assert_eq!(inventory, before);
+    }
+
+    #[test]
+    fn any_of_slots_take_the_first_alternative_on_hand() {
+        let (ore, coal, charcoal, ingot) = (0, 1, 2, 3);
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(ore, 1)],
+            vec![RecipeComponent::new(ingot, 1)],
+            4000,
+            vec![],
+        )
+        .with_any_of(vec![vec![
+            RecipeComponent::new(coal, 2),
+            RecipeComponent::new(charcoal, 1),
+        ]]);
+        let mut inventory = Inventory::new();
+        inventory.set(ore, 3);
+        inventory.set(charcoal, 1);
+        assert!(recipe.can_craft(&inventory));
+        recipe.craft(&mut inventory).unwrap();
+        assert_eq!(inventory.quantity(ore), 2);
+        assert_eq!(inventory.quantity(charcoal), 0);
+        assert_eq!(inventory.quantity(ingot), 1);
+
+        inventory.set(coal, 2);
+        inventory.set(charcoal, 1);
+        recipe.craft(&mut inventory).unwrap();
+        assert_eq!(inventory.quantity(coal), 0);
+        assert_eq!(inventory.quantity(charcoal), 1);
+
+        inventory.set(charcoal, 0);
+        assert!(!recipe.can_craft(&inventory));
+        assert_eq!(
+            recipe.craft(&mut inventory),
+            Err(CraftError::Insufficient {
+                resource_id: coal,
+                required: 2,
+                available: 0
+            })
+        );
+    }
+
+    #[test]
+    fn craft_with_shared_resource_and_overflow() {
+        // A catalyst is consumed and given back
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(0, 1), RecipeComponent::new(1, 3)],
+            vec![RecipeComponent::new(0, 1), RecipeComponent::new(2, 1)],
+            4000,
+            vec![],
+        );
+        let mut inventory = Inventory::new();
+        inventory.set(1, 3);
+        assert!(!recipe.can_craft(&inventory));
+        inventory.set(0, 1);
+        recipe.craft(&mut inventory).unwrap();
+        assert_eq!(inventory.quantity(0), 1);
+        assert_eq!(inventory.quantity(1), 0);
+
+        inventory.set(1, 3);
+        inventory.set(2, usize::MAX);
+        let before = inventory.clone();
+        assert_eq!(recipe.craft(&mut inventory), Err(CraftError::Overflow(2)));
+        assert_eq!(inventory, before);
+    }
+
+    fn assert_round_trips(json: &str) {
+        let original: Value = serde_json::from_str(json).unwrap();
+        let pattern: RecipePattern = serde_json::from_value(original.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&pattern).unwrap(), original);
+    }
+
+    #[test]
+    fn named_pattern_round_trips() {
+        assert_round_trips(
+            r#"{
+                "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        );
+    }
+
+    #[test]
+    fn tag_pattern_round_trips() {
+        assert_round_trips(
+            r#"{
+                "input": [{ "resource": { "tags": ["Ore"] }, "quantity": 1 }],
+                "output": [{ "resource": "{input0} Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "setup_time": 500,
+                "primary": true,
+                "requirements": ["Building: Furnace", { "MinLevel": 3 }]
+            }"#,
+        );
+    }
 
+    #[test]
+    fn unknown_resource_is_an_error() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let pattern: RecipePattern = serde_json::from_str(
+            r#"{
+                "input": [{ "resource": "Unobtainium", "quantity": 1 }],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pattern.into_recipes(&manager).unwrap_err(),
+            RecipeError::UnknownResource("Unobtainium".to_string())
+        );
+    }
+
+    #[test]
+    fn fully_named_pattern_makes_one_recipe() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let pattern: RecipePattern = serde_json::from_str(
+            r#"{
+                "input": [{ "resource": "Iron Ore", "quantity": 2 }],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        )
+        .unwrap();
+        assert!(pattern.is_fully_named());
+
+        let recipes = pattern.into_recipes(&manager).unwrap();
+        let expected = Recipe::new(
+            vec![RecipeComponent::new(manager.resource_id_by_name("Iron Ore").unwrap(), 2)],
+            vec![RecipeComponent::new(manager.resource_id_by_name("Iron Ingot").unwrap(), 1)],
+            4000,
+            vec![],
+        );
+        assert_eq!(recipes, vec![expected]);
+    }
+
+    fn pattern(json: &str) -> RecipePattern {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn tag_pattern_makes_a_recipe_per_match() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let recipes = pattern(
+            r#"{
+                "input": [{ "resource": { "tags": ["Ore"] }, "quantity": 2 }],
+                "output": [{ "resource": "Iron", "quantity": 1 }],
+                "base_time": 4000,
+                "setup_time": 500,
+                "requirements": []
+            }"#,
+        )
+        .into_recipes(&manager)
+        .unwrap();
+
+        let iron = manager.resource_id_by_name("Iron").unwrap();
+        let mut ores = manager
+            .resources()
+            .filter(|res| res.contains_tag(&ResourceTag::Ore))
+            .map(|res| res.id())
+            .collect::<Vec<_>>();
+        ores.sort_unstable();
+        let expected = ores
+            .into_iter()
+            .map(|ore| {
+                Recipe::new(
+                    vec![RecipeComponent::new(ore, 2)],
+                    vec![RecipeComponent::new(iron, 1)],
+                    4000,
+                    vec![],
+                )
+                .with_setup_time(500)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 3);
+        assert_eq!(recipes, expected);
+    }
+
+    #[test]
+    fn unmatched_tag_filter_makes_no_recipes() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let recipes = pattern(
+            r#"{
+                "input": [{ "resource": { "tags": ["Ore", "Ingot"] }, "quantity": 1 }],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        )
+        .into_recipes(&manager)
+        .unwrap();
+        assert!(recipes.is_empty());
+    }
+
+    #[test]
+    fn custom_tags_filter_patterns() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let coal = manager
+            .add_resource(Resource::new(
+                "Coal",
+                "",
+                "",
+                iced::Color::WHITE,
+                iced::Color::BLACK,
+                &[ResourceTag::Custom("Fuel".to_string())],
+            ))
+            .unwrap();
+        let recipes = pattern(
+            r#"{
+                "input": [{ "resource": { "tags": ["Fuel"] }, "quantity": 1 }],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        )
+        .into_recipes(&manager)
+        .unwrap();
+        assert_eq!(recipes.len(), 1);
+        assert_eq!(recipes[0].inputs()[0].resource_id(), coal);
+    }
+
+    #[test]
+    fn any_of_is_read_from_patterns() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut fuel = |name: &str| {
+            manager
+                .add_resource(Resource::new(name, "", "", Color::WHITE, Color::BLACK, &[]))
+                .unwrap()
+        };
+        let coal = fuel("Coal");
+        let charcoal = fuel("Charcoal");
+        let recipes = pattern(
+            r#"{
+                "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                "any_of": [[
+                    { "resource": "Coal", "quantity": 2 },
+                    { "resource": "Charcoal", "quantity": 1 }
+                ]],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        )
+        .into_recipes(&manager)
+        .unwrap();
+        assert_eq!(
+            recipes[0].any_of(),
+            &vec![vec![
+                RecipeComponent::new(coal, 2),
+                RecipeComponent::new(charcoal, 1)
+            ]]
+        );
+        assert!(recipes[0].references(charcoal));
+    }
+
+    #[test]
+    fn tag_filters_honor_implications() {
+        let mut manager = ResourceManager::new();
+        manager
+            .add_tag_implication(ResourceTag::Ore, ResourceTag::Custom("Raw".to_string()))
+            .unwrap();
+        setup_resource_manager(&mut manager).unwrap();
+        let recipes = pattern(
+            r#"{
+                "input": [{ "resource": { "tags": ["Raw"] }, "quantity": 1 }],
+                "output": [{ "resource": "Iron", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        )
+        .into_recipes(&manager)
+        .unwrap();
+        assert_eq!(recipes.len(), 3);
+    }
+
+    fn input_ids(manager: &ResourceManager, filter: &str) -> Result<Vec<u64>, RecipeError> {
+        let recipes = pattern(&format!(
+            r#"{{
+                "input": [{{ "resource": {}, "quantity": 1 }}],
+                "output": [{{ "resource": "Iron", "quantity": 1 }}],
+                "base_time": 4000,
+                "requirements": []
+            }}"#,
+            filter
+        ))
+        .into_recipes(manager)?;
+        Ok(recipes
+            .iter()
+            .map(|recipe| recipe.inputs()[0].resource_id())
+            .collect())
+    }
+
+    #[test]
+    fn tag_filter_modes() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let ids =
+            |resources: Vec<&Resource>| resources.iter().map(|res| res.id()).collect::<Vec<_>>();
+
+        assert_eq!(
+            input_ids(&manager, r#"{ "tags": ["Ore", "Ingot"], "mode": "any" }"#).unwrap(),
+            ids(manager.resources_with_any_tag(&[ResourceTag::Ore, ResourceTag::Ingot]))
+        );
+        assert_eq!(
+            input_ids(
+                &manager,
+                r#"{ "tags": ["Metal"], "exclude_tags": ["Ingot", "Ore"], "mode": "all" }"#
+            )
+            .unwrap(),
+            ids(manager.resources_matching(
+                &[ResourceTag::Metal],
+                &[ResourceTag::Ingot, ResourceTag::Ore]
+            ))
+        );
+        assert!(input_ids(&manager, r#"{ "exclude_tags": ["Metal"] }"#)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn output_templates_name_the_chosen_input() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let smelt = |output: &str| {
+            pattern(&format!(
+                r#"{{
+                    "input": [{{ "resource": {{ "tags": ["Base", "Metal"] }}, "quantity": 2 }}],
+                    "output": [{{ "resource": "{}", "quantity": 1 }}],
+                    "base_time": 4000,
+                    "requirements": []
+                }}"#,
+                output
+            ))
+            .into_recipes(&manager)
+        };
+
+        let recipes = smelt("{input0} Ingot").unwrap();
+        assert_eq!(recipes.len(), 3);
+        for recipe in &recipes {
+            let input = manager
+                .resource_by_id(recipe.inputs()[0].resource_id())
+                .unwrap();
+            let output = manager
+                .resource_by_id(recipe.outputs()[0].resource_id())
+                .unwrap();
+            assert_eq!(output.name(), &format!("{} Ingot", input.name()));
+            assert_eq!(recipe.outputs()[0].quantity(), 1);
+        }
+
+        assert_eq!(
+            smelt("{input0} Gem").unwrap_err(),
+            RecipeError::UnknownResource("Iron Gem".to_string())
+        );
+        assert_eq!(
+            smelt("{input1} Ingot").unwrap_err(),
+            RecipeError::MalformedComponent(Value::from("{input1} Ingot"))
+        );
+    }
+
+    #[test]
+    fn requirements_are_parsed() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let with_requirements = |requirements: &str| {
+            pattern(&format!(
+                r#"{{
+                    "input": [{{ "resource": "Iron Ore", "quantity": 1 }}],
+                    "output": [{{ "resource": "Iron Ingot", "quantity": 1 }}],
+                    "base_time": 4000,
+                    "requirements": {}
+                }}"#,
+                requirements
+            ))
+            .into_recipes(&manager)
+        };
+
+        let recipes = with_requirements(
+            r#"["Building: Furnace", "TechUnlocked:Smelting", { "MinLevel": 5 }, "MinLevel: 2"]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            recipes[0].requirements(),
+            &vec![
+                Requirement::Building("Furnace".to_string()),
+                Requirement::TechUnlocked("Smelting".to_string()),
+                Requirement::MinLevel(5),
+                Requirement::MinLevel(2),
+            ]
+        );
+
+        for unknown in [
+            r#""Furnace""#,
+            r#""Weather: Rain""#,
+            r#""MinLevel: high""#,
+            r#"{ "Season": "Winter" }"#,
+            "4",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                with_requirements(&format!("[{}]", unknown)).unwrap_err(),
+                RecipeError::UnknownRequirement(serde_json::from_str(unknown).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn reordered_combinations_are_one_recipe() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let recipes = pattern(
+            r#"{
+                "input": [
+                    { "resource": { "tags": ["Base"] }, "quantity": 1 },
+                    { "resource": { "tags": ["Base"] }, "quantity": 1 }
+                ],
+                "output": [{ "resource": "Iron Wire", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }"#,
+        )
+        .into_recipes(&manager)
+        .unwrap();
+        // Three base metals taken two at a time with repetition, ignoring order
+        assert_eq!(recipes.len(), 6);
+    }
+
+    #[test]
+    fn selectors_read_names_and_tag_filters() {
+        let component: RecipePatternComponent =
+            serde_json::from_str(r#"{ "resource": "Iron Ore", "quantity": 2 }"#).unwrap();
+        assert_eq!(
+            component.selector(),
+            &ResourceSelector::Named("Iron Ore".to_string())
+        );
+
+        let component: RecipePatternComponent = serde_json::from_str(
+            r#"{ "resource": { "tags": ["Metal"], "exclude_tags": ["Ore"], "mode": "any" }, "quantity": 1 }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            component.selector(),
+            &ResourceSelector::ByTags {
+                tags: vec![ResourceTag::Metal],
+                exclude: vec![ResourceTag::Ore],
+                mode: TagMatch::Any,
+            }
+        );
+
+        assert!(serde_json::from_str::<RecipePatternComponent>(
+            r#"{ "resource": { "tags": ["Ore"], "mode": "some" }, "quantity": 1 }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn invalid_selectors_are_errors() {
+        let read = |json: &str| {
+            ResourceSelector::from_value(&serde_json::from_str(json).unwrap()).unwrap_err()
+        };
+        assert_eq!(
+            read(r#"{ "tags": ["Ore", 3] }"#),
+            RecipeError::InvalidTag(Value::from(3))
+        );
+        assert_eq!(
+            read(r#"{ "tags": [""] }"#),
+            RecipeError::InvalidTag(Value::from(""))
+        );
+        assert_eq!(
+            read(r#"{ "tags": "Ore" }"#),
+            RecipeError::MalformedComponent(serde_json::json!({ "tags": "Ore" }))
+        );
+        assert_eq!(
+            read(r#"{ "tag": ["Ore"] }"#),
+            RecipeError::UnknownKey("tag".to_string())
+        );
+        assert_eq!(
+            read(r#"{ "tags": ["Ore"], "mode": "some" }"#),
+            RecipeError::MalformedComponent(serde_json::json!({ "tags": ["Ore"], "mode": "some" }))
+        );
+        assert_eq!(read("7"), RecipeError::MalformedComponent(Value::from(7)));
+    }
+
+    #[test]
+    fn profit_margin_compares_output_and_input_values() {
+        let mut manager = ResourceManager::new();
+        let mut add = |name: &str, value: f64| {
+            let resource =
+                Resource::new(name, "", "", Color::WHITE, Color::BLACK, &[]).with_base_value(value);
+            manager.add_resource(resource).unwrap()
+        };
+        let ore = add("Iron Ore", 1.5);
+        let ingot = add("Iron Ingot", 5.0);
+        let slag = add("Slag", 0.0);
+
+        let smelt = Recipe::new(
+            vec![RecipeComponent::new(ore, 2)],
+            vec![
+                RecipeComponent::new(ingot, 1),
+                RecipeComponent::new(slag, 1),
+            ],
+            4000,
+            vec![],
+        );
+        assert_eq!(smelt.profit_margin(&manager), Ok(2.0));
+
+        let scrap = Recipe::new(
+            vec![RecipeComponent::new(ingot, 1)],
+            vec![RecipeComponent::new(ore, 1)],
+            1000,
+            vec![],
+        );
+        assert_eq!(scrap.profit_margin(&manager), Ok(-3.5));
+
+        let lucky = Recipe::new(
+            vec![RecipeComponent::new(ore, 2)],
+            vec![RecipeComponent::new(ingot, 1).with_chance(0.5)],
+            4000,
+            vec![],
+        );
+        assert_eq!(lucky.profit_margin(&manager), Ok(-0.5));
+
+        let missing = Recipe::new(vec![], vec![RecipeComponent::new(99, 1)], 1000, vec![]);
+        assert!(missing.profit_margin(&manager).is_err());
+        assert_eq!(missing.profit(&manager), 0.0);
+    }
+
+    #[test]
+    fn any_of_slots_cost_their_cheapest_alternative() {
+        let mut manager = ResourceManager::new();
+        let mut add = |name: &str, value: f64| {
+            let resource =
+                Resource::new(name, "", "", Color::WHITE, Color::BLACK, &[]).with_base_value(value);
+            manager.add_resource(resource).unwrap()
+        };
+        let ore = add("Iron Ore", 1.5);
+        let coal = add("Coal", 1.0);
+        let charcoal = add("Charcoal", 3.0);
+        let ingot = add("Iron Ingot", 8.0);
+
+        let smelt = Recipe::new(
+            vec![RecipeComponent::new(ore, 2)],
+            vec![RecipeComponent::new(ingot, 1)],
+            4000,
+            vec![],
+        )
+        .with_any_of(vec![vec![
+            RecipeComponent::new(charcoal, 1),
+            RecipeComponent::new(coal, 2),
+        ]]);
+        assert_eq!(smelt.profit_margin(&manager), Ok(3.0));
+        assert_eq!(smelt.profit(&manager), 3.0);
+    }
+
+    #[test]
+    fn profit_leaves_out_catalysts() {
+        let mut manager = ResourceManager::new();
+        let mut add = |name: &str, value: f64| {
+            let resource =
+                Resource::new(name, "", "", Color::WHITE, Color::BLACK, &[]).with_base_value(value);
+            manager.add_resource(resource).unwrap()
+        };
+        let ore = add("Iron Ore", 1.0);
+        let ingot = add("Iron Ingot", 4.0);
+        let furnace = add("Furnace", 50.0);
+
+        let smelt = Recipe::new(
+            vec![RecipeComponent::new(ore, 2)],
+            vec![RecipeComponent::new(ingot, 1)],
+            4000,
+            vec![],
+        )
+        .with_catalysts(vec![RecipeComponent::new(furnace, 1)]);
+        assert_eq!(smelt.profit(&manager), 2.0);
+    }
+
+    #[test]
+    fn byproducts_are_rolled_for() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(0, 1)],
+            vec![
+                RecipeComponent::new(1, 1),
+                RecipeComponent::new(2, 1).with_chance(0.0),
+                RecipeComponent::new(3, 1).with_chance(1.0),
+            ],
+            4000,
+            vec![],
+        );
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut inventory = Inventory::new();
+        inventory.set(0, 100);
+        for _ in 0..100 {
+            let produced = recipe.craft_with_rng(&mut inventory, &mut rng).unwrap();
+            assert_eq!(
+                produced,
+                vec![RecipeComponent::new(1, 1), RecipeComponent::new(3, 1)]
+            );
+        }
+        assert_eq!(inventory.quantity(2), 0);
+        assert_eq!(inventory.quantity(3), 100);
+
+        let pattern: RecipePatternComponent =
+            serde_json::from_str(r#"{"resource": "Slag", "quantity": 1, "chance": 0.1}"#).unwrap();
+        assert_eq!(pattern.resolve(2).chance(), 0.1);
+        let component: RecipeComponent =
+            serde_json::from_str(r#"{"resource_id": 2, "quantity": 1}"#).unwrap();
+        assert_eq!(component.chance(), 1.0);
+    }
+
+    #[test]
+    fn even_chances_produce_about_half_the_time() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(0, 1)],
+            vec![RecipeComponent::new(1, 2).with_chance(0.5)],
+            4000,
+            vec![],
+        );
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut inventory = Inventory::new();
+        inventory.set(0, 1000);
+        for _ in 0..1000 {
+            recipe.craft_with_rng(&mut inventory, &mut rng).unwrap();
+        }
+        assert_eq!(inventory.quantity(0), 0);
+        let produced = inventory.quantity(1);
+        assert_eq!(produced % 2, 0);
+        assert!((900..=1100).contains(&produced), "produced {}", produced);
+
+        let read = |json| {
+            serde_json::from_str::<RecipeComponent>(json)
+                .unwrap()
+                .chance()
+        };
+        assert_eq!(
+            read(r#"{"resource_id": 1, "quantity": 1, "chance": 1.5}"#),
+            1.0
+        );
+        assert_eq!(
+            read(r#"{"resource_id": 1, "quantity": 1, "chance": -0.5}"#),
+            0.0
+        );
+        let pattern: RecipePatternComponent =
+            serde_json::from_str(r#"{"resource": "Slag", "quantity": 1, "chance": 2}"#).unwrap();
+        assert_eq!(pattern.resolve(2).chance(), 1.0);
+        let never = RecipeComponent::new(1, 1).with_chance(f32::NAN);
+        assert_eq!(never, RecipeComponent::new(1, 1).with_chance(-0.0));
+    }
+
+    #[test]
+    fn catalysts_are_needed_but_kept() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let pattern: RecipePattern = serde_json::from_value(serde_json::json!({
+            "input": [{"resource": "Iron Plate", "quantity": 1}],
+            "output": [{"resource": "Iron Wire", "quantity": 2}],
+            "catalysts": [{"resource": "Iron Ingot", "quantity": 1}],
+            "base_time": 1000,
+            "requirements": []
+        }))
+        .unwrap();
+        let recipe = pattern.into_recipes(&manager).unwrap().remove(0);
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        assert_eq!(
+            recipe.catalysts(),
+            &vec![RecipeComponent::new(id("Iron Ingot"), 1)]
+        );
+        assert!(recipe.references(id("Iron Ingot")));
+
+        let mut inventory = Inventory::new();
+        inventory.set(id("Iron Plate"), 1);
+        assert!(!recipe.can_craft(&inventory));
+        assert!(recipe.craft(&mut inventory).is_err());
+
+        inventory.set(id("Iron Ingot"), 1);
+        recipe.craft(&mut inventory).unwrap();
+        assert_eq!(inventory.quantity(id("Iron Ingot")), 1);
+        assert_eq!(inventory.quantity(id("Iron Plate")), 0);
+        assert_eq!(inventory.quantity(id("Iron Wire")), 2);
+    }
+
+    #[test]
+    fn catalyst_is_needed_on_top_of_same_input() {
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(0, 2)],
+            vec![RecipeComponent::new(1, 1)],
+            4000,
+            vec![],
+        )
+        .with_catalysts(vec![RecipeComponent::new(0, 1)]);
+        let mut inventory = Inventory::new();
+        inventory.set(0, 2);
+        assert_eq!(
+            recipe.craft(&mut inventory),
+            Err(CraftError::Insufficient {
+                resource_id: 0,
+                required: 3,
+                available: 2
+            })
+        );
+        inventory.set(0, 3);
+        recipe.craft(&mut inventory).unwrap();
+        assert_eq!(inventory.quantity(0), 1);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::processing::recipe::Recipe;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The resources used by a set of recipes, with an edge from every input of a recipe to each of
+/// its outputs. A recipe giving back one of its inputs, such as a catalyst, adds no edge from that
+/// resource to itself.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RecipeGraph {
+    edges: BTreeMap<u64, BTreeSet<u64>>,
+}
+
+impl RecipeGraph {
+    pub fn new<'r, I: IntoIterator<Item = &'r Recipe>>(recipes: I) -> Self {
+        let mut edges: BTreeMap<u64, BTreeSet<u64>> = BTreeMap::new();
+        for recipe in recipes {
+            for component in recipe.inputs().iter().chain(recipe.outputs().iter()) {
+                edges.entry(component.resource_id()).or_default();
+            }
+            for input in recipe.inputs() {
+                for output in recipe.outputs() {
+                    if input.resource_id() != output.resource_id() {
+                        edges
+                            .get_mut(&input.resource_id())
+                            .unwrap()
+                            .insert(output.resource_id());
+                    }
+                }
+            }
+        }
+        RecipeGraph { edges }
+    }
+
+    /// Every resource in the graph, ordered by id
+    pub fn resources(&self) -> impl Iterator<Item = u64> + '_ {
+        self.edges.keys().copied()
+    }
+
+    /// The resources directly made from a resource, ordered by id
+    pub fn outputs_of(&self, resource_id: u64) -> impl Iterator<Item = u64> + '_ {
+        self.edges.get(&resource_id).into_iter().flatten().copied()
+    }
+
+    /// Orders the resources so that every resource comes after the resources it is made from,
+    /// breaking ties by id. If the recipes form a cycle, the resources on cycles, or between
+    /// them, are returned instead, ordered by id.
+    pub fn topological_order(&self) -> Result<Vec<u64>, Vec<u64>> {
+        topological_sort(&self.edges)
+    }
+}
+
+/// Orders the nodes of a graph, given as the nodes each node has an edge to, so that every node
+/// comes after the nodes with an edge to it. Every node must be a key of `edges`. Ties are broken
+/// by the nodes' order. If the graph has a cycle, the nodes on cycles, or between them, are
+/// returned instead, in order.
+pub(crate) fn topological_sort<N: Ord + Copy>(
+    edges: &BTreeMap<N, BTreeSet<N>>,
+) -> Result<Vec<N>, Vec<N>> {
+    let outputs_of = |node: N| edges.get(&node).into_iter().flatten().copied();
+    let mut remaining_inputs: BTreeMap<N, usize> = edges.keys().map(|&node| (node, 0)).collect();
+    for outputs in edges.values() {
+        for output in outputs {
+            *remaining_inputs.get_mut(output).unwrap() += 1;
+        }
+    }
+
+    let mut ready = remaining_inputs
+        .iter()
+        .filter(|(_, &count)| count == 0)
+        .map(|(&node, _)| node)
+        .collect::<BTreeSet<_>>();
+    let mut order = Vec::with_capacity(edges.len());
+    while let Some(node) = ready.iter().next().copied() {
+        ready.remove(&node);
+        order.push(node);
+        for output in outputs_of(node) {
+            let count = remaining_inputs.get_mut(&output).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.insert(output);
+            }
+        }
+    }
+    if order.len() == edges.len() {
+        return Ok(order);
+    }
+
+    // What's left is the cycles and everything after them, so drop nodes that lead nowhere else
+    // in what's left until only the cycles remain
+    let mut cyclic = remaining_inputs
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(node, _)| node)
+        .collect::<BTreeSet<_>>();
+    loop {
+        let dead_ends = cyclic
+            .iter()
+            .copied()
+            .filter(|&node| !outputs_of(node).any(|output| cyclic.contains(&output)))
+            .collect::<Vec<_>>();
+        if dead_ends.is_empty() {
+            break;
+        }
+        for node in dead_ends {
+            cyclic.remove(&node);
+        }
+    }
+    Err(cyclic.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::recipe::RecipeComponent;
+
+    fn make(input: u64, output: u64) -> Recipe {
+        Recipe::new(
+            vec![RecipeComponent::new(input, 1)],
+            vec![RecipeComponent::new(output, 1)],
+            4000,
+            vec![],
+        )
+    }
+
+    #[test]
+    fn tree_is_ordered_from_its_roots() {
+        // Ore 0 is smelted into ingot 1, which is made into plate 2 and wire 3
+        let recipes = vec![make(1, 3), make(0, 1), make(1, 2)];
+        let graph = RecipeGraph::new(&recipes);
+        assert_eq!(graph.topological_order(), Ok(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn cycle_members_are_reported() {
+        // 1 and 2 are made from each other, and 3 is made from 2
+        let recipes = vec![make(0, 1), make(1, 2), make(2, 1), make(2, 3)];
+        let graph = RecipeGraph::new(&recipes);
+        assert_eq!(graph.topological_order(), Err(vec![1, 2]));
+
+        let catalyst = Recipe::new(
+            vec![RecipeComponent::new(4, 1), RecipeComponent::new(0, 1)],
+            vec![RecipeComponent::new(4, 1), RecipeComponent::new(5, 1)],
+            4000,
+            vec![],
+        );
+        assert!(RecipeGraph::new(&[catalyst]).topological_order().is_ok());
+    }
+}
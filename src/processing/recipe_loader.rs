@@ -1,46 +1,2419 @@
-use crate::processing::recipe::{Recipe, RecipePattern};
+use crate::processing::recipe::{Recipe, RecipeComponent, RecipeError, RecipePattern};
+use crate::processing::recipe_graph::{topological_sort, RecipeGraph};
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, BufWriter, Read};
 use std::path::{Path, PathBuf};
-use crate::production::resource::ResourceManager;
-use std::collections::HashMap;
-use serde_json::Value;
+use crate::production::inventory::Inventory;
+use crate::production::resource::{
+    Resource, ResourceDef, ResourceError, ResourceManager, ResourceTag,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use serde_json::{json, Value};
+#[cfg(feature = "hot-reload")]
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+#[cfg(feature = "hot-reload")]
+use std::ffi::OsStr;
+#[cfg(feature = "hot-reload")]
+use std::sync::mpsc::{channel, Receiver};
+#[cfg(feature = "hot-reload")]
+use std::time::Duration;
+use std::fmt::{Display, Formatter};
+
+/// Watches the files of a recipe source, started by [`RecipeLoader::watch`]. Watching stops
+/// when it's dropped.
+#[cfg(feature = "hot-reload")]
+pub struct RecipeWatcher {
+    /// Only held to keep the events coming
+    _watcher: RecommendedWatcher,
+    changes: Receiver<()>,
+}
+
+#[cfg(feature = "hot-reload")]
+impl RecipeWatcher {
+    /// Waits up to `timeout` for the source to change, returning whether it did
+    pub fn wait(&self, timeout: Duration) -> bool {
+        self.changes.recv_timeout(timeout).is_ok()
+    }
+
+    /// Whether the source changed since the last call, without waiting
+    pub fn changed(&self) -> bool {
+        self.changes.try_iter().count() > 0
+    }
+}
+
+/// The key a recipe was loaded under in the recipe file
+pub type RecipeKey = String;
+
+/// A summary of what a call to [`RecipeLoader::load_recipes`] loaded
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LoadSummary {
+    pub files: usize,
+    pub patterns: usize,
+    pub recipes: usize,
+    /// Non-fatal issues, such as a recipe identical to one already loaded under the same key
+    pub warnings: Vec<String>,
+}
+
+impl Display for LoadSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "loaded {} recipes from {} files with {} warnings",
+            self.recipes,
+            self.files,
+            self.warnings.len()
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found while validating the loaded recipes
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Somewhere recipe patterns can be loaded from, keyed by recipe name
+pub trait RecipeSource {
+    fn load(&self) -> Result<HashMap<String, RecipePattern>, Box<dyn Error>>;
+
+    /// How many files the recipes are loaded from
+    fn files(&self) -> usize {
+        1
+    }
+
+    /// The file or directory the recipes are loaded from, if they come from the filesystem
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// The file formats recipe patterns can be written in
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RecipeFormat {
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl RecipeFormat {
+    /// Picks the format of a recipe file from its extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(RecipeFormat::Json),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(RecipeFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Reads recipe patterns keyed by recipe name in this format
+    pub fn read<R: Read>(
+        self,
+        reader: R,
+    ) -> Result<HashMap<String, RecipePattern>, Box<dyn Error>> {
+        match self {
+            RecipeFormat::Json => Ok(serde_json::from_reader(reader)?),
+            #[cfg(feature = "yaml")]
+            RecipeFormat::Yaml => Ok(serde_yaml::from_reader(reader)?),
+        }
+    }
+}
+
+/// Loads recipe patterns from a file in the format given by its extension, or from JSON if the
+/// extension isn't recognized
+impl RecipeSource for PathBuf {
+    fn load(&self) -> Result<HashMap<String, RecipePattern>, Box<dyn Error>> {
+        let file = File::open(self)?;
+        let reader = BufReader::new(file);
+        RecipeFormat::from_path(self)
+            .unwrap_or(RecipeFormat::Json)
+            .read(reader)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(self)
+    }
+}
+
+/// Loads recipe patterns from every recipe file in a directory, each holding recipes keyed by
+/// name as in a single recipe file. A key may only be used by one file.
+#[derive(Debug, Clone)]
+pub struct RecipeDirectory {
+    path: PathBuf,
+}
+
+impl RecipeDirectory {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        RecipeDirectory {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Gets the recipe files in the directory, in name order
+    fn recipe_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if path.is_file() && RecipeFormat::from_path(&path).is_some() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+impl RecipeSource for RecipeDirectory {
+    fn load(&self) -> Result<HashMap<String, RecipePattern>, Box<dyn Error>> {
+        let mut recipes = HashMap::new();
+        for file in self.recipe_files()? {
+            for (key, pattern) in file.load()? {
+                if recipes.contains_key(&key) {
+                    return Err(RecipeError::DuplicateKey(key, file).into());
+                }
+                recipes.insert(key, pattern);
+            }
+        }
+        Ok(recipes)
+    }
+
+    fn files(&self) -> usize {
+        self.recipe_files().map_or(0, |files| files.len())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// A JSON file holding both resources and recipes, for packs too small to be worth two files.
+/// The `"resources"` key holds a list of resources as in a resources file, and the `"recipes"`
+/// key holds recipes keyed by name as in a recipe file. As a [`RecipeSource`] only the recipes
+/// are loaded; see [`load_all`] to add the resources as well.
+#[derive(Debug, Clone)]
+pub struct CombinedFile {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Combined {
+    #[serde(default)]
+    resources: Vec<ResourceDef>,
+    #[serde(default)]
+    recipes: HashMap<String, RecipePattern>,
+}
+
+impl CombinedFile {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        CombinedFile {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn read(&self) -> Result<Combined, Box<dyn Error>> {
+        let file = File::open(&self.path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+}
+
+impl RecipeSource for CombinedFile {
+    fn load(&self) -> Result<HashMap<String, RecipePattern>, Box<dyn Error>> {
+        Ok(self.read()?.recipes)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// Loads a [combined file](CombinedFile), adding its resources to `manager` before loading its
+/// recipes, so recipes can use the resources defined beside them. Returns the loader holding the
+/// recipes, which can [reload](RecipeLoader::reload) them later, and what was loaded. Resources
+/// added before an error are kept.
+pub fn load_all<P: AsRef<Path>>(
+    path: P,
+    manager: &mut ResourceManager,
+) -> Result<(RecipeLoader<'_, CombinedFile>, LoadSummary), Box<dyn Error>> {
+    let source = CombinedFile::new(path);
+    manager.add_resource_defs(source.read()?.resources)?;
+    let mut loader = RecipeLoader::with_source(source, manager);
+    let summary = loader.load_recipes()?;
+    Ok((loader, summary))
+}
 
 #[derive(Debug)]
-pub struct RecipeLoader<'a> {
-    file_path: PathBuf,
-    created_recipes: HashMap<String, Recipe>,
-    resource_manager: &'a ResourceManager
+pub struct RecipeLoader<'a, S: RecipeSource = PathBuf> {
+    source: S,
+    /// The recipes loaded under each key, as one pattern can expand to many recipes
+    created_recipes: HashMap<RecipeKey, Vec<Recipe>>,
+    /// The keys of the recipes producing each resource
+    producers: HashMap<u64, BTreeSet<RecipeKey>>,
+    /// The keys of the recipes needing each resource on hand, as an input, an `any_of` alternative
+    /// or a catalyst
+    consumers: HashMap<u64, BTreeSet<RecipeKey>>,
+    resource_manager: &'a ResourceManager,
+    overlap_severity: Severity,
 }
 
 impl<'a> RecipeLoader<'a> {
     pub fn new<P: AsRef<Path>>(file_path: P, manager: &'a ResourceManager) -> Self {
+        Self::with_source(PathBuf::from(file_path.as_ref()), manager)
+    }
+}
+
+impl<'a> RecipeLoader<'a, RecipeDirectory> {
+    pub fn from_directory<P: AsRef<Path>>(dir: P, manager: &'a ResourceManager) -> Self {
+        Self::with_source(RecipeDirectory::new(dir), manager)
+    }
+
+    /// Loads the recipes of every file in the directory
+    pub fn load_all(&mut self) -> Result<LoadSummary, Box<dyn Error>> {
+        self.load_recipes()
+    }
+}
+
+impl<'a, S: RecipeSource> RecipeLoader<'a, S> {
+    pub fn with_source(source: S, manager: &'a ResourceManager) -> Self {
         RecipeLoader {
-            file_path: PathBuf::from(file_path.as_ref()),
+            source,
             created_recipes: Default::default(),
-            resource_manager: manager
+            producers: Default::default(),
+            consumers: Default::default(),
+            resource_manager: manager,
+            overlap_severity: Severity::Warning,
         }
     }
 
-    pub fn load_recipes(&mut self) -> Result<(), Box<dyn Error>> {
-        let file = File::open(&self.file_path)?;
-        let mut reader = BufReader::new(file);
-        let recipes: HashMap<String, RecipePattern> = serde_json::from_reader(reader)?;
-        //self.created_recipes.extend(recipes);
-        println!("{:?}", recipes);
-        let new_recipes = recipes
-            .into_iter()
-            .flat_map(|(key, val)|
-                val.into_recipes(self.resource_manager)
-                    .into_iter()
-                    .map(|recipe| (key.clone(), recipe))
-                    .collect::<Vec<(_, _)>>()
+    /// The resources the recipes are loaded against
+    pub fn resource_manager(&self) -> &'a ResourceManager {
+        self.resource_manager
+    }
+
+    /// Loads the recipes of the source alongside those already loaded. A key that is already
+    /// loaded with different recipes is an error, as is any other recipe error, and leaves the
+    /// loaded recipes as they were. Use [`RecipeLoader::reload`] to load a changed source again.
+    pub fn load_recipes(&mut self) -> Result<LoadSummary, Box<dyn Error>> {
+        let recipes = self.source.load()?;
+        let files = self.source.files();
+        let origin = self
+            .source
+            .path()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        self.expand_patterns(recipes, files, Some(origin))
+    }
+
+    /// Loads the recipes again from the source, replacing every loaded recipe. If the source can't
+    /// be loaded, the recipes already loaded are kept as they were.
+    pub fn reload(&mut self) -> Result<LoadSummary, Box<dyn Error>> {
+        let recipes = self.source.load()?;
+        let files = self.source.files();
+        self.expand_patterns(recipes, files, None)
+    }
+
+    /// Loads recipe patterns in the recipe file format from a reader instead of the source, such
+    /// as recipes embedded in the binary. As with [`RecipeLoader::load_recipes`], a key that is
+    /// already loaded with different recipes is an error.
+    pub fn load_recipes_from_reader<R: Read>(
+        &mut self,
+        reader: R,
+    ) -> Result<LoadSummary, Box<dyn Error>> {
+        let recipes = RecipeFormat::Json.read(reader)?;
+        self.expand_patterns(recipes, 0, Some(PathBuf::new()))
+    }
+
+    /// Expands the patterns into recipes. Given the `origin` the patterns were read from, they're
+    /// added to the loaded recipes and a key that's already loaded with different recipes is an
+    /// error. Without one, they replace every loaded recipe. Nothing changes if any pattern can't
+    /// be expanded.
+    fn expand_patterns(
+        &mut self,
+        recipes: HashMap<String, RecipePattern>,
+        files: usize,
+        origin: Option<PathBuf>,
+    ) -> Result<LoadSummary, Box<dyn Error>> {
+        let mut summary = LoadSummary {
+            files,
+            patterns: recipes.len(),
+            ..Default::default()
+        };
+        let mut new_recipes = Vec::new();
+        for (key, val) in recipes {
+            new_recipes.push((key, val.into_recipes(self.resource_manager)?));
+        }
+        match origin {
+            Some(origin) => {
+                let mut duplicates: Vec<_> = new_recipes
+                    .iter()
+                    .filter(|(key, recipes)| {
+                        self.created_recipes
+                            .get(key)
+                            .is_some_and(|loaded| loaded != recipes)
+                    })
+                    .map(|(key, _)| key)
+                    .collect();
+                duplicates.sort();
+                if let Some(key) = duplicates.first() {
+                    return Err(RecipeError::DuplicateKey(key.to_string(), origin).into());
+                }
+            }
+            None => {
+                self.created_recipes.clear();
+                self.producers.clear();
+                self.consumers.clear();
+            }
+        }
+        for (key, recipes) in new_recipes {
+            if self.created_recipes.get(&key) == Some(&recipes) {
+                summary
+                    .warnings
+                    .push(format!("Recipe {} is identical to an already loaded recipe", key));
+            }
+            summary.recipes += recipes.len();
+            self.insert_recipes(key, recipes);
+        }
+        Ok(summary)
+    }
+
+    /// Starts watching the source's file or directory for changes from a background thread,
+    /// returning at once. The returned watcher reports the changes, after which the recipes can be
+    /// loaded again with [`RecipeLoader::poll_reload`]. Watching stops when the watcher is dropped.
+    ///
+    /// The recipes aren't reloaded on the watching thread itself, as the loader only borrows its
+    /// resource manager and so can't be handed to another thread.
+    #[cfg(feature = "hot-reload")]
+    pub fn watch(&self) -> Result<RecipeWatcher, Box<dyn Error>> {
+        let path = self
+            .source
+            .path()
+            .ok_or("The recipe source isn't in the filesystem")?
+            .to_path_buf();
+        // Editors often replace a file rather than write to it, so the file's directory is watched
+        let (watched, file_name) = if path.is_dir() {
+            (path.clone(), None)
+        } else {
+            let parent = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty());
+            (
+                parent.unwrap_or_else(|| Path::new(".")).to_path_buf(),
+                path.file_name().map(OsStr::to_os_string),
             )
+        };
+        let (event_sender, events) = channel();
+        let mut watcher = notify::watcher(event_sender, Duration::from_millis(100))?;
+        watcher.watch(&watched, RecursiveMode::NonRecursive)?;
+
+        let (change_sender, changes) = channel();
+        // Ends once the watcher is dropped, which closes the event channel
+        std::thread::spawn(move || {
+            for event in events {
+                let changed = match event {
+                    DebouncedEvent::Create(changed)
+                    | DebouncedEvent::Write(changed)
+                    | DebouncedEvent::Rename(_, changed) => changed,
+                    _ => continue,
+                };
+                let relevant = file_name
+                    .as_deref()
+                    .map_or(true, |name| changed.file_name() == Some(name));
+                if relevant && change_sender.send(()).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(RecipeWatcher {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Loads the recipes again with [`reload`](Self::reload) if the watcher has seen the source
+    /// change since the last poll, passing how that went to `callback`, and returns whether it
+    /// reloaded. Call it regularly, such as once a frame, to pick up changes as they're made.
+    #[cfg(feature = "hot-reload")]
+    pub fn poll_reload(
+        &mut self,
+        watcher: &RecipeWatcher,
+        callback: impl Fn(&Result<(), RecipeError>),
+    ) -> bool {
+        if !watcher.changed() {
+            return false;
+        }
+        let result =
+            self.reload()
+                .map(|_| ())
+                .map_err(|error| match error.downcast::<RecipeError>() {
+                    Ok(error) => *error,
+                    Err(error) => RecipeError::Unloadable(error.to_string()),
+                });
+        callback(&result);
+        true
+    }
+
+    /// Sets the recipes of a key, replacing any recipes it had, and keeps the producer and
+    /// consumer indexes up to date
+    fn insert_recipes(&mut self, key: RecipeKey, recipes: Vec<Recipe>) {
+        for recipe in self.created_recipes.remove(&key).into_iter().flatten() {
+            for component in recipe.outputs() {
+                if let Some(keys) = self.producers.get_mut(&component.resource_id()) {
+                    keys.remove(&key);
+                }
+            }
+            for component in recipe.input_slots().flatten().chain(recipe.catalysts()) {
+                if let Some(keys) = self.consumers.get_mut(&component.resource_id()) {
+                    keys.remove(&key);
+                }
+            }
+        }
+        for recipe in &recipes {
+            for component in recipe.outputs() {
+                self.producers
+                    .entry(component.resource_id())
+                    .or_default()
+                    .insert(key.clone());
+            }
+            for component in recipe.input_slots().flatten().chain(recipe.catalysts()) {
+                self.consumers
+                    .entry(component.resource_id())
+                    .or_default()
+                    .insert(key.clone());
+            }
+        }
+        self.created_recipes.insert(key, recipes);
+    }
+
+    /// Takes every loaded recipe along with its key, ordered by key, such as to hand them to
+    /// something that outlives the loader's borrow of the resource manager
+    pub fn into_recipes(self) -> Vec<(RecipeKey, Recipe)> {
+        let mut recipes = self
+            .created_recipes
+            .into_iter()
+            .flat_map(|(key, recipes)| recipes.into_iter().map(move |recipe| (key.clone(), recipe)))
             .collect::<Vec<_>>();
-        self.created_recipes.extend(new_recipes);
+        // Stable, so recipes under one key stay in the order they were loaded
+        recipes.sort_by(|(left, _), (right, _)| left.cmp(right));
+        recipes
+    }
+
+    /// Every loaded recipe along with its key
+    fn all_recipes(&self) -> impl Iterator<Item = (&RecipeKey, &Recipe)> + Clone {
+        self.created_recipes
+            .iter()
+            .flat_map(|(key, recipes)| recipes.iter().map(move |recipe| (key, recipe)))
+    }
+
+    /// Gets every recipe with the resource as an output, ordered by key
+    pub fn recipes_producing(&self, resource_id: u64) -> Vec<&Recipe> {
+        self.indexed_recipes(&self.producers, resource_id, |recipe| {
+            Box::new(recipe.outputs().iter())
+        })
+    }
+
+    /// Gets every recipe needing the resource on hand, as an input, an `any_of` alternative or a
+    /// catalyst, ordered by key
+    pub fn recipes_consuming(&self, resource_id: u64) -> Vec<&Recipe> {
+        self.indexed_recipes(&self.consumers, resource_id, |recipe| {
+            Box::new(recipe.input_slots().flatten().chain(recipe.catalysts()))
+        })
+    }
+
+    fn indexed_recipes(
+        &self,
+        index: &HashMap<u64, BTreeSet<RecipeKey>>,
+        resource_id: u64,
+        side: fn(&Recipe) -> Box<dyn Iterator<Item = &RecipeComponent> + '_>,
+    ) -> Vec<&Recipe> {
+        index
+            .get(&resource_id)
+            .into_iter()
+            .flatten()
+            .flat_map(|key| &self.created_recipes[key])
+            .filter(|recipe| side(recipe).any(|component| component.resource_id() == resource_id))
+            .collect()
+    }
+
+    /// Writes every loaded recipe to a JSON file as an object ordered by recipe key
+    pub fn save_compiled<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let recipes = self.created_recipes.iter().collect::<BTreeMap<_, _>>();
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &recipes)?;
         Ok(())
     }
+
+    /// Gets the keys of every recipe that consumes or produces the resource, sorted by key
+    pub fn recipes_referencing(&self, resource_id: u64) -> Vec<RecipeKey> {
+        let mut keys = self
+            .created_recipes
+            .iter()
+            .filter(|(_, recipes)| recipes.iter().any(|recipe| recipe.references(resource_id)))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    /// Gets every resource that can be obtained from the base resources using only the given recipes
+    fn craftable_closure<'r, I>(&self, base: &HashSet<u64>, recipes: I) -> HashSet<u64>
+    where
+        I: IntoIterator<Item = &'r Recipe> + Clone,
+    {
+        let mut obtainable = base.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for recipe in recipes.clone() {
+                if recipe.craftable_from(|id| obtainable.contains(&id)) {
+                    for output in recipe.outputs() {
+                        changed |= obtainable.insert(output.resource_id());
+                    }
+                }
+            }
+        }
+        obtainable
+    }
+
+    /// Gets the keys of the recipes whose inputs only become fully available once the new
+    /// resource is added to the already available ones, sorted by key. Catalysts count as
+    /// inputs, and an `any_of` slot is available once any of its alternatives is. A key is listed once even if several of its
+    /// recipes are enabled.
+    pub fn recipes_enabled_by(
+        &self,
+        newly_available: u64,
+        already_available: &HashSet<u64>,
+    ) -> Vec<RecipeKey> {
+        let mut keys = self
+            .all_recipes()
+            .filter(|(_, recipe)| {
+                !already_available.contains(&newly_available)
+                    && !recipe.craftable_from(|id| already_available.contains(&id))
+                    && recipe.craftable_from(|id| {
+                        id == newly_available || already_available.contains(&id)
+                    })
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    /// Gets the ids of every resource that can't be obtained from the base resources when only
+    /// the unlocked recipes can be used, sorted by id
+    pub fn currently_unobtainable(&self, unlocked: &HashSet<RecipeKey>, base: &HashSet<u64>) -> Vec<u64> {
+        let unlocked_recipes = self
+            .all_recipes()
+            .filter(|(key, _)| unlocked.contains(*key))
+            .map(|(_, recipe)| recipe)
+            .collect::<Vec<_>>();
+        let obtainable = self.craftable_closure(base, unlocked_recipes.iter().copied());
+        let mut unobtainable = self
+            .resource_manager
+            .resources()
+            .map(|resource| resource.id())
+            .filter(|id| !obtainable.contains(id))
+            .collect::<Vec<_>>();
+        unobtainable.sort_unstable();
+        unobtainable
+    }
+
+    /// Finds every pair of keys with identical recipes. Each pair is ordered by key, and the pairs
+    /// are sorted and listed once.
+    pub fn duplicate_recipes(&self) -> Vec<(RecipeKey, RecipeKey)> {
+        let mut recipes = self.all_recipes().collect::<Vec<_>>();
+        recipes.sort_by_key(|(key, _)| *key);
+        let mut duplicates = Vec::new();
+        for (index, (key, recipe)) in recipes.iter().enumerate() {
+            for (other_key, other_recipe) in &recipes[index + 1..] {
+                if key != other_key && recipe == other_recipe {
+                    duplicates.push(((*key).clone(), (*other_key).clone()));
+                }
+            }
+        }
+        duplicates.sort();
+        duplicates.dedup();
+        duplicates
+    }
+
+    /// Gets the minimum number of crafting steps needed to reach each resource from the base
+    /// resources, which are at distance 0. A recipe's outputs are one step further than the
+    /// furthest of its inputs and catalysts, where an `any_of` slot is as far as its nearest
+    /// alternative.
+    /// Unreachable resources are left out.
+    pub fn distance_from_base(&self, base: &HashSet<u64>) -> HashMap<u64, u32> {
+        let mut distances = base.iter().map(|&id| (id, 0)).collect::<HashMap<_, _>>();
+        let mut step = 0;
+        loop {
+            step += 1;
+            let mut reached = Vec::new();
+            for (_, recipe) in self.all_recipes() {
+                if !recipe.craftable_from(|id| distances.contains_key(&id)) {
+                    continue;
+                }
+                for output in recipe.outputs() {
+                    if !distances.contains_key(&output.resource_id()) {
+                        reached.push(output.resource_id());
+                    }
+                }
+            }
+            if reached.is_empty() {
+                return distances;
+            }
+            for id in reached {
+                distances.insert(id, step);
+            }
+        }
+    }
+
+    /// Chooses the recipe used to produce a resource, preferring primary recipes, then the lowest
+    /// key, then the first of that key's recipes
+    fn chosen_producer(&self, resource_id: u64) -> Option<(&RecipeKey, &Recipe)> {
+        self.all_recipes()
+            .filter(|(_, recipe)| {
+                recipe
+                    .outputs()
+                    .iter()
+                    .any(|output| output.resource_id() == resource_id)
+            })
+            .min_by(|(left_key, left), (right_key, right)| {
+                right
+                    .is_primary()
+                    .cmp(&left.is_primary())
+                    .then_with(|| left_key.cmp(right_key))
+            })
+    }
+
+    /// Gets how much of each raw resource it takes to make one of a resource. Resources are
+    /// expanded through the recipe picked by [`chosen_producer`](Self::chosen_producer), so
+    /// primary recipes win and ties go to the lowest key. Crafts are whole, so making less than a
+    /// recipe's output still costs a full craft, though everything needing the same resource
+    /// shares its crafts. An `any_of` slot is paid for with its first alternative, as when
+    /// crafting without any of them on hand. Expansion stops at resources tagged `Base`, resources
+    /// no recipe produces, and resources needed while they're being expanded, which would
+    /// otherwise recurse forever through a cycle.
+    pub fn total_base_cost(&self, resource_id: u64) -> HashMap<u64, usize> {
+        self.raw_demand(resource_id, 1.0, |needed, produced| {
+            (needed / produced).ceil()
+        })
+        .into_iter()
+        .map(|(id, amount)| (id, amount.round() as usize))
+        .collect()
+    }
+
+    /// Finds the resource that most limits how much of `target` can be made from what's
+    /// available: the raw resource whose available quantity, divided by how much of it one of
+    /// `target` takes, is smallest. Raw resources are found as for
+    /// [`total_base_cost`](Self::total_base_cost), but without rounding to whole crafts. Ties go
+    /// to the lowest id. Gives `None` if making the target takes nothing.
+    pub fn bottleneck(&self, target: u64, available: &Inventory) -> Option<u64> {
+        self.raw_demand(target, 1.0, |needed, produced| needed / produced)
+            .into_iter()
+            .filter(|&(_, demand)| demand > 0.0)
+            .map(|(id, demand)| (id, available.quantity(id) as f64 / demand))
+            .min_by(|(left_id, left), (right_id, right)| {
+                left.partial_cmp(right)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| left_id.cmp(right_id))
+            })
+            .map(|(id, _)| id)
+    }
+
+    /// Spreads a demand for `amount` of a resource over the raw resources it's made from, as
+    /// described by [`total_base_cost`](Self::total_base_cost). The demand for a resource is
+    /// summed over everything needing it before `crafts` turns it, and how much one craft makes,
+    /// into how many crafts are needed.
+    fn raw_demand(
+        &self,
+        resource_id: u64,
+        amount: f64,
+        crafts: impl Fn(f64, f64) -> f64,
+    ) -> HashMap<u64, f64> {
+        let mut order = vec![];
+        let mut cyclic = HashSet::new();
+        self.order_expansion(
+            resource_id,
+            &mut vec![],
+            &mut HashSet::new(),
+            &mut order,
+            &mut cyclic,
+        );
+
+        let mut needed = HashMap::new();
+        needed.insert(resource_id, amount);
+        let mut raw = HashMap::new();
+        // Everything needing a resource comes before it, so its demand is complete when reached
+        for &id in order.iter().rev() {
+            let amount = needed.get(&id).copied().unwrap_or(0.0);
+            match self.expansion(id) {
+                Some((producer, produced)) => {
+                    let crafts = crafts(amount, produced);
+                    for input in producer.input_slots().filter_map(<[_]>::first) {
+                        let demand = if cyclic.contains(&(id, input.resource_id())) {
+                            &mut raw
+                        } else {
+                            &mut needed
+                        };
+                        *demand.entry(input.resource_id()).or_insert(0.0) +=
+                            input.quantity() as f64 * crafts;
+                    }
+                }
+                None => *raw.entry(id).or_insert(0.0) += amount,
+            }
+        }
+        raw
+    }
+
+    /// The recipe a resource is expanded through, and how much of the resource one craft of it
+    /// makes, or `None` if the resource is raw
+    fn expansion(&self, resource_id: u64) -> Option<(&Recipe, f64)> {
+        let is_base = self
+            .resource_manager
+            .resource_by_id(resource_id)
+            .is_some_and(|res| res.contains_tag(&ResourceTag::Base));
+        if is_base {
+            return None;
+        }
+        let (_, producer) = self.chosen_producer(resource_id)?;
+        let produced = producer
+            .outputs()
+            .iter()
+            .filter(|output| output.resource_id() == resource_id)
+            .map(|output| output.quantity() as f64 * output.chance() as f64)
+            .sum::<f64>();
+        if produced == 0.0 {
+            return None;
+        }
+        Some((producer, produced))
+    }
+
+    /// Lists the resources expanded from a resource, each after everything it is needed by. An
+    /// input needed while it's being expanded is recorded in `cyclic` along with what needs it.
+    fn order_expansion(
+        &self,
+        resource_id: u64,
+        path: &mut Vec<u64>,
+        visited: &mut HashSet<u64>,
+        order: &mut Vec<u64>,
+        cyclic: &mut HashSet<(u64, u64)>,
+    ) {
+        visited.insert(resource_id);
+        path.push(resource_id);
+        if let Some((producer, _)) = self.expansion(resource_id) {
+            for input in producer.input_slots().filter_map(<[_]>::first) {
+                let input = input.resource_id();
+                if path.contains(&input) {
+                    cyclic.insert((resource_id, input));
+                } else if !visited.contains(&input) {
+                    self.order_expansion(input, path, visited, order, cyclic);
+                }
+            }
+        }
+        path.pop();
+        order.push(resource_id);
+    }
+
+    /// Builds the crafting tree of a resource as JSON. Each node has the resource's `id` and
+    /// `name`, and resources with a producing recipe also have the `recipe` key and the `inputs`
+    /// it needs, each expanded the same way with its `quantity`. Shared subtrees are repeated, and
+    /// a resource that depends on itself is an error naming the recipe keys on the cycle.
+    pub fn tree_json(&self, target: u64) -> Result<Value, ResourceError> {
+        self.tree_json_inner(target, &mut Vec::new())
+    }
+
+    fn tree_json_inner<'s>(
+        &'s self,
+        target: u64,
+        path: &mut Vec<(u64, &'s RecipeKey)>,
+    ) -> Result<Value, ResourceError> {
+        let resource = self.resource_manager.resource_by_id_res(target)?;
+        if let Some(start) = path.iter().position(|&(id, _)| id == target) {
+            let keys = path[start..].iter().map(|(_, key)| key.to_string());
+            return Err(ResourceError::RecipeCycle(keys.collect()));
+        }
+
+        let mut node = json!({ "id": target, "name": resource.name() });
+        if let Some((key, recipe)) = self.chosen_producer(target) {
+            path.push((target, key));
+            let mut inputs = Vec::new();
+            for input in recipe.inputs() {
+                let mut input_node = self.tree_json_inner(input.resource_id(), path)?;
+                input_node["quantity"] = json!(input.quantity());
+                inputs.push(input_node);
+            }
+            path.pop();
+            node["recipe"] = json!(key);
+            node["inputs"] = Value::Array(inputs);
+        }
+        Ok(node)
+    }
+
+    /// Builds the graph of which resources are made from which, over every loaded recipe
+    pub fn graph(&self) -> RecipeGraph {
+        RecipeGraph::new(self.all_recipes().map(|(_, recipe)| recipe))
+    }
+
+    /// Orders the recipe keys so that every key comes after the keys with recipes producing its
+    /// inputs, including every `any_of` alternative. Ties are broken by key. Recipes of a key producing the inputs of that same key don't
+    /// make it depend on itself, but any longer cycle is an error naming the keys on it.
+    pub fn topological_order(&self) -> Result<Vec<RecipeKey>, ResourceError> {
+        let ids = |side: fn(&Recipe) -> Box<dyn Iterator<Item = &RecipeComponent> + '_>| {
+            self.created_recipes
+                .iter()
+                .map(|(key, recipes)| {
+                    let ids = recipes
+                        .iter()
+                        .flat_map(side)
+                        .map(RecipeComponent::resource_id)
+                        .collect::<HashSet<_>>();
+                    (key, ids)
+                })
+                .collect::<HashMap<_, _>>()
+        };
+        let outputs = ids(|recipe| Box::new(recipe.outputs().iter()));
+        let inputs = ids(|recipe| Box::new(recipe.input_slots().flatten()));
+        let mut dependents: BTreeMap<&RecipeKey, BTreeSet<&RecipeKey>> = BTreeMap::new();
+        for (&producer_key, produced) in &outputs {
+            let consumers = dependents.entry(producer_key).or_default();
+            for (&consumer_key, consumed) in &inputs {
+                if producer_key != consumer_key && !produced.is_disjoint(consumed) {
+                    consumers.insert(consumer_key);
+                }
+            }
+        }
+
+        topological_sort(&dependents)
+            .map(|order| order.into_iter().cloned().collect())
+            .map_err(|cyclic| ResourceError::RecipeCycle(cyclic.into_iter().cloned().collect()))
+    }
+
+    fn resource_name(&self, resource_id: u64) -> String {
+        self.resource_manager
+            .resource_by_id(resource_id)
+            .map(|resource| resource.name().clone())
+            .unwrap_or_else(|| resource_id.to_string())
+    }
+
+    /// Gets the resources of the base set that some recipe produces, sorted by id. Such resources
+    /// aren't truly base in a closed economy.
+    pub fn validate_base_resources(&self, base: &HashSet<u64>) -> Vec<u64> {
+        let mut produced = self
+            .all_recipes()
+            .flat_map(|(_, recipe)| recipe.outputs().iter().map(|output| output.resource_id()))
+            .filter(|id| base.contains(id))
+            .collect::<Vec<_>>();
+        produced.sort_unstable();
+        produced.dedup();
+        produced
+    }
+
+    /// Sets how recipes using a resource as both an input and an output are reported
+    pub fn set_overlap_severity(&mut self, severity: Severity) {
+        self.overlap_severity = severity;
+    }
+
+    /// Checks the loaded recipes for problems, sorted by message. A resource with more than one
+    /// primary producer is always an error, while a recipe whose outputs overlap its inputs is
+    /// reported with the configured overlap severity, a warning by default.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let mut primary_producers: HashMap<u64, Vec<&RecipeKey>> = HashMap::new();
+        for (key, recipe) in self.all_recipes() {
+            if !recipe.is_primary() {
+                continue;
+            }
+            for output in recipe.outputs() {
+                primary_producers.entry(output.resource_id()).or_default().push(key);
+            }
+        }
+        for (resource_id, mut producers) in primary_producers {
+            if producers.len() < 2 {
+                continue;
+            }
+            producers.sort();
+            producers.dedup();
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                message: format!(
+                    "{} has multiple primary producers: {}",
+                    self.resource_name(resource_id),
+                    producers
+                        .iter()
+                        .map(|key| key.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            });
+        }
+
+        for (key, recipe) in self.all_recipes() {
+            for output in recipe.outputs() {
+                let overlaps = recipe
+                    .inputs()
+                    .iter()
+                    .any(|input| input.resource_id() == output.resource_id());
+                if overlaps {
+                    diagnostics.push(Diagnostic {
+                        severity: self.overlap_severity,
+                        message: format!(
+                            "Recipe {} uses {} as both an input and an output",
+                            key,
+                            self.resource_name(output.resource_id())
+                        ),
+                    });
+                }
+            }
+        }
+
+        diagnostics.sort_by(|left, right| left.message.cmp(&right.message));
+        diagnostics.dedup();
+        diagnostics
+    }
+
+    /// Checks every loaded recipe, returning all the problems found rather than just the first.
+    /// Each recipe must only use resources that exist, in nonzero quantities, including every
+    /// `any_of` alternative, take some time to
+    /// craft and have requirements that can be met. These come first, ordered by key, followed by
+    /// every error found by [`diagnostics`](Self::diagnostics).
+    pub fn validate(&self) -> Vec<RecipeError> {
+        let mut recipes = self.all_recipes().collect::<Vec<_>>();
+        recipes.sort_by_key(|(key, _)| *key);
+        let mut errors = Vec::new();
+        for (key, recipe) in recipes {
+            let components = recipe
+                .input_slots()
+                .flatten()
+                .chain(recipe.outputs())
+                .chain(recipe.catalysts());
+            for component in components {
+                let id = component.resource_id();
+                if self.resource_manager.resource_by_id(id).is_none() {
+                    errors.push(RecipeError::MissingResource(key.clone(), id));
+                }
+                if component.quantity() == 0 {
+                    errors.push(RecipeError::ZeroQuantity(key.clone(), id));
+                }
+            }
+            if recipe.base_time() == 0 {
+                errors.push(RecipeError::ImplausibleTime(
+                    key.clone(),
+                    recipe.base_time(),
+                ));
+            }
+            for requirement in recipe.requirements() {
+                if !requirement.is_satisfiable() {
+                    errors.push(RecipeError::UnsatisfiableRequirement(
+                        key.clone(),
+                        requirement.clone(),
+                    ));
+                }
+            }
+        }
+        errors.extend(
+            self.diagnostics()
+                .into_iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .map(|diagnostic| RecipeError::Conflict(diagnostic.message)),
+        );
+        errors
+    }
+
+    /// Sets the base time of every recipe loaded under the key
+    pub fn set_base_time(&mut self, key: &str, time: u16) -> Result<(), ResourceError> {
+        let recipes = self
+            .created_recipes
+            .get_mut(key)
+            .ok_or_else(|| ResourceError::UnknownRecipe(key.to_string()))?;
+        for recipe in recipes {
+            recipe.set_base_time(time);
+        }
+        Ok(())
+    }
+
+    /// Gets the [profit margin](Recipe::profit_margin) of every loaded recipe along with its key,
+    /// most profitable first, with ties ordered by key
+    pub fn profit_margins(&self) -> Result<Vec<(&RecipeKey, f64)>, ResourceError> {
+        let mut margins = self
+            .all_recipes()
+            .map(|(key, recipe)| {
+                recipe
+                    .profit_margin(self.resource_manager)
+                    .map(|m| (key, m))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        margins.sort_by(|&a, &b| by_profit(a, b));
+        Ok(margins)
+    }
+
+    /// Gets the `n` loaded recipes with the highest [profit](Recipe::profit), along with their
+    /// keys, most profitable first, with ties ordered by key
+    pub fn most_profitable_recipes(&self, n: usize) -> Vec<(&RecipeKey, &Recipe)> {
+        let mut profits = self
+            .all_recipes()
+            .map(|(key, recipe)| (key, recipe, recipe.profit(self.resource_manager)))
+            .collect::<Vec<_>>();
+        profits.sort_by(|&(a_key, _, a), &(b_key, _, b)| by_profit((a_key, a), (b_key, b)));
+        profits
+            .into_iter()
+            .take(n)
+            .map(|(key, recipe, _)| (key, recipe))
+            .collect()
+    }
+
+    /// Multiplies the base time of every loaded recipe by `factor`, saturating at the bounds of `u16`
+    pub fn scale_all_times(&mut self, factor: f64) {
+        for recipe in self.created_recipes.values_mut().flatten() {
+            let scaled = (recipe.base_time() as f64 * factor).round();
+            recipe.set_base_time(scaled.clamp(0.0, u16::MAX as f64) as u16);
+        }
+    }
+
+    /// Sets the base value of every resource made by a recipe, other than those tagged `Base`,
+    /// to what its cheapest recipe's inputs are worth per output made, with each `any_of` slot
+    /// at its cheapest alternative. Outputs count by their chance of being produced, and recipes
+    /// are worked through in [topological order](Self::topological_order) so their inputs are
+    /// valued first. Values are read from and written to `manager`, which can't be the one this
+    /// loader borrows.
+    pub fn compute_derived_values(
+        &self,
+        manager: &mut ResourceManager,
+    ) -> Result<(), ResourceError> {
+        let mut derived = BTreeMap::<u64, f64>::new();
+        for key in self.topological_order()? {
+            for recipe in &self.created_recipes[&key] {
+                let produced = recipe
+                    .outputs()
+                    .iter()
+                    .map(|output| output.quantity() as f64 * output.chance() as f64)
+                    .sum::<f64>();
+                if produced == 0.0 {
+                    continue;
+                }
+                let cost = recipe.input_cost(|id| match derived.get(&id) {
+                    Some(&value) => Ok(value),
+                    None => manager.resource_by_id_res(id).map(Resource::base_value),
+                })?;
+                for output in recipe.outputs() {
+                    let resource = manager.resource_by_id_res(output.resource_id())?;
+                    if resource.contains_tag(&ResourceTag::Base) {
+                        continue;
+                    }
+                    let value = derived.entry(output.resource_id()).or_insert(f64::INFINITY);
+                    *value = value.min(cost / produced);
+                }
+            }
+        }
+        for (id, value) in derived {
+            manager.update_resource(id, |res| *res = res.clone().with_base_value(value))?;
+        }
+        Ok(())
+    }
+}
+/// Orders keyed profits from most to least profitable, with ties ordered by key
+fn by_profit(a: (&RecipeKey, f64), b: (&RecipeKey, f64)) -> std::cmp::Ordering {
+    b.1.partial_cmp(&a.1)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.0.cmp(b.0))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::recipe::Requirement;
+    use crate::production::resource::{setup_resource_manager, Resource};
+    use crate::test_util::TempDir;
+    use iced::Color;
+
+    fn smelt_recipe(manager: &ResourceManager, base_time: u16) -> Recipe {
+        let mut recipe = recipe_between(manager, "Iron Ore", "Iron Ingot");
+        recipe.set_base_time(base_time);
+        recipe
+    }
+
+    #[test]
+    fn set_base_time_updates_recipe() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader
+            .created_recipes
+            .insert("smelt".to_string(), vec![smelt_recipe(&manager, 4000)]);
+
+        loader.set_base_time("smelt", 1000).unwrap();
+        assert_eq!(loader.created_recipes["smelt"][0].base_time(), 1000);
+        assert_eq!(
+            loader.set_base_time("missing", 1000),
+            Err(ResourceError::UnknownRecipe("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn scale_all_times_halves_times() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader
+            .created_recipes
+            .insert("smelt".to_string(), vec![smelt_recipe(&manager, 4000)]);
+        loader
+            .created_recipes
+            .insert("slow smelt".to_string(), vec![smelt_recipe(&manager, 5001)]);
+
+        loader.scale_all_times(0.5);
+        assert_eq!(loader.created_recipes["smelt"][0].base_time(), 2000);
+        assert_eq!(loader.created_recipes["slow smelt"][0].base_time(), 2501);
+
+        let recipes = loader.into_recipes();
+        assert_eq!(recipes[0].0, "slow smelt");
+        assert_eq!(recipes[1].0, "smelt");
+    }
+
+    #[test]
+    fn profit_margins_rank_recipes() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        for (name, value) in [("Iron Ore", 1.0), ("Iron Ingot", 3.0)].iter() {
+            let id = manager.resource_id_by_name(name).unwrap();
+            manager
+                .update_resource(id, |res| *res = res.clone().with_base_value(*value))
+                .unwrap();
+        }
+        let melt = Recipe::new(
+            smelt_recipe(&manager, 4000).outputs().clone(),
+            smelt_recipe(&manager, 4000).inputs().clone(),
+            4000,
+            vec![],
+        );
+        let mut loader = RecipeLoader::new("", &manager);
+        loader
+            .created_recipes
+            .insert("melt".to_string(), vec![melt]);
+        loader
+            .created_recipes
+            .insert("smelt".to_string(), vec![smelt_recipe(&manager, 4000)]);
+
+        let smelt = "smelt".to_string();
+        let melt = "melt".to_string();
+        assert_eq!(
+            loader.profit_margins(),
+            Ok(vec![(&smelt, 2.0), (&melt, -2.0)])
+        );
+    }
+
+    #[test]
+    fn profitable_recipes_rank_first() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        for (name, value) in [("Iron Ore", 1.0), ("Iron Ingot", 3.0), ("Iron Plate", 3.0)].iter() {
+            let id = manager.resource_id_by_name(name).unwrap();
+            manager
+                .update_resource(id, |res| *res = res.clone().with_base_value(*value))
+                .unwrap();
+        }
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "compress".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+        loader.insert_recipes("smelt".to_string(), vec![smelt_recipe(&manager, 4000)]);
+
+        let ranked = loader
+            .most_profitable_recipes(2)
+            .into_iter()
+            .map(|(key, recipe)| (key.as_str(), recipe.profit(&manager)))
+            .collect::<Vec<_>>();
+        assert_eq!(ranked, vec![("smelt", 2.0), ("compress", 0.0)]);
+        assert_eq!(loader.most_profitable_recipes(1).len(), 1);
+        assert!(loader.most_profitable_recipes(0).is_empty());
+    }
+
+    #[test]
+    fn removing_ore_affects_smelting() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader
+            .created_recipes
+            .insert("smelt iron".to_string(), vec![smelt_recipe(&manager, 4000)]);
+
+        let iron_ore = manager.resource_id_by_name("Iron Ore").unwrap();
+        let iron_ingot = manager.resource_id_by_name("Iron Ingot").unwrap();
+        let gold_ore = manager.resource_id_by_name("Gold Ore").unwrap();
+        assert_eq!(loader.recipes_referencing(iron_ore), vec!["smelt iron".to_string()]);
+        assert_eq!(loader.recipes_referencing(iron_ingot), vec!["smelt iron".to_string()]);
+        assert!(loader.recipes_referencing(gold_ore).is_empty());
+    }
+
+    #[test]
+    fn locking_smelting_makes_ingots_unobtainable() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader
+            .created_recipes
+            .insert("smelt iron".to_string(), vec![smelt_recipe(&manager, 4000)]);
+
+        let iron_ore = manager.resource_id_by_name("Iron Ore").unwrap();
+        let iron_ingot = manager.resource_id_by_name("Iron Ingot").unwrap();
+        let base = [iron_ore].iter().copied().collect::<HashSet<_>>();
+
+        let locked = loader.currently_unobtainable(&HashSet::new(), &base);
+        assert!(locked.contains(&iron_ingot));
+        assert!(!locked.contains(&iron_ore));
+
+        let unlocked = ["smelt iron".to_string()].iter().cloned().collect::<HashSet<_>>();
+        let locked = loader.currently_unobtainable(&unlocked, &base);
+        assert!(!locked.contains(&iron_ingot));
+        assert!(!locked.contains(&iron_ore));
+    }
+
+    #[test]
+    fn any_of_inputs_gate_obtainability() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let (ore, ingot, copper, gold) =
+            (id("Iron Ore"), id("Iron Ingot"), id("Copper"), id("Gold"));
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "smelt".to_string(),
+            vec![
+                recipe_between(&manager, "Iron Ore", "Iron Ingot").with_any_of(vec![vec![
+                    RecipeComponent::new(copper, 1),
+                    RecipeComponent::new(gold, 1),
+                ]]),
+            ],
+        );
+        let set = |ids: &[u64]| ids.iter().copied().collect::<HashSet<_>>();
+        let unlocked = ["smelt".to_string()]
+            .iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        assert!(loader
+            .currently_unobtainable(&unlocked, &set(&[ore]))
+            .contains(&ingot));
+        assert!(!loader.distance_from_base(&set(&[ore])).contains_key(&ingot));
+        assert!(!loader
+            .currently_unobtainable(&unlocked, &set(&[ore, gold]))
+            .contains(&ingot));
+        assert_eq!(loader.distance_from_base(&set(&[ore, gold]))[&ingot], 1);
+
+        assert_eq!(
+            loader.recipes_enabled_by(gold, &set(&[ore])),
+            vec!["smelt".to_string()]
+        );
+        assert!(loader
+            .recipes_enabled_by(gold, &set(&[ore, copper]))
+            .is_empty());
+        assert_eq!(loader.recipes_consuming(gold).len(), 1);
+        assert_eq!(
+            loader.topological_order().unwrap(),
+            vec!["smelt".to_string()]
+        );
+        assert_eq!(
+            loader.total_base_cost(ingot),
+            vec![(ore, 1), (copper, 1)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn catalysts_are_needed_but_not_paid_for() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let (ore, ingot, copper) = (id("Iron Ore"), id("Iron Ingot"), id("Copper"));
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")
+                .with_catalysts(vec![RecipeComponent::new(copper, 1)])],
+        );
+        let set = |ids: &[u64]| ids.iter().copied().collect::<HashSet<_>>();
+        let unlocked = ["smelt".to_string()]
+            .iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        assert!(loader
+            .currently_unobtainable(&unlocked, &set(&[ore]))
+            .contains(&ingot));
+        assert!(!loader.distance_from_base(&set(&[ore])).contains_key(&ingot));
+        assert!(!loader
+            .currently_unobtainable(&unlocked, &set(&[ore, copper]))
+            .contains(&ingot));
+        assert_eq!(loader.distance_from_base(&set(&[ore, copper]))[&ingot], 1);
+        assert_eq!(
+            loader.recipes_enabled_by(copper, &set(&[ore])),
+            vec!["smelt".to_string()]
+        );
+        assert_eq!(loader.recipes_consuming(copper).len(), 1);
+
+        assert_eq!(
+            loader.total_base_cost(ingot),
+            vec![(ore, 1)].into_iter().collect()
+        );
+    }
+
+    fn recipe_between(manager: &ResourceManager, input: &str, output: &str) -> Recipe {
+        recipe_with(manager, &[(input, 1)], &[(output, 1)])
+    }
+
+    #[test]
+    fn topological_order_follows_chain() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "compress".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")],
+        );
+        assert_eq!(
+            loader.topological_order().unwrap(),
+            vec!["smelt".to_string(), "compress".to_string()]
+        );
+
+        loader.created_recipes.insert(
+            "unsmelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Ore")],
+        );
+        assert_eq!(
+            loader.topological_order(),
+            Err(ResourceError::RecipeCycle(vec![
+                "smelt".to_string(),
+                "unsmelt".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn validate_flags_multiple_primary_producers() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![smelt_recipe(&manager, 4000).with_primary(true)],
+        );
+        loader
+            .created_recipes
+            .insert("slow smelt".to_string(), vec![smelt_recipe(&manager, 8000)]);
+        assert!(loader.validate().is_empty());
+
+        loader.created_recipes.insert(
+            "slow smelt".to_string(),
+            vec![smelt_recipe(&manager, 8000).with_primary(true)],
+        );
+        assert_eq!(
+            loader.validate(),
+            vec![RecipeError::Conflict(
+                "Iron Ingot has multiple primary producers: slow smelt, smelt".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        let iron = manager.resource_id_by_name("Iron").unwrap();
+        let ingot = manager.resource_id_by_name("Iron Ingot").unwrap();
+        let recipe = |inputs, time, requirements| {
+            Recipe::new(
+                inputs,
+                vec![RecipeComponent::new(ingot, 1)],
+                time,
+                requirements,
+            )
+        };
+        loader
+            .created_recipes
+            .insert("fine".to_string(), vec![smelt_recipe(&manager, 4000)]);
+        loader.created_recipes.insert(
+            "ghost".to_string(),
+            vec![recipe(vec![RecipeComponent::new(999, 1)], 4000, vec![])],
+        );
+        loader.created_recipes.insert(
+            "free".to_string(),
+            vec![recipe(vec![RecipeComponent::new(iron, 0)], 4000, vec![])],
+        );
+        loader.created_recipes.insert(
+            "instant".to_string(),
+            vec![recipe(vec![RecipeComponent::new(iron, 1)], 0, vec![])],
+        );
+        let nowhere = Requirement::Building(String::new());
+        loader.created_recipes.insert(
+            "impossible".to_string(),
+            vec![recipe(
+                vec![RecipeComponent::new(iron, 1)],
+                4000,
+                vec![Requirement::MinLevel(3), nowhere.clone()],
+            )],
+        );
+        assert_eq!(
+            loader.validate(),
+            vec![
+                RecipeError::ZeroQuantity("free".to_string(), iron),
+                RecipeError::MissingResource("ghost".to_string(), 999),
+                RecipeError::UnsatisfiableRequirement("impossible".to_string(), nowhere),
+                RecipeError::ImplausibleTime("instant".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn distance_from_base_counts_steps() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")],
+        );
+        loader.created_recipes.insert(
+            "compress".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let base = [id("Iron Ore")].iter().copied().collect::<HashSet<_>>();
+
+        let distances = loader.distance_from_base(&base);
+        assert_eq!(distances[&id("Iron Ore")], 0);
+        assert_eq!(distances[&id("Iron Ingot")], 1);
+        assert_eq!(distances[&id("Iron Plate")], 2);
+        assert!(!distances.contains_key(&id("Gold Ingot")));
+    }
+
+    fn recipe_with(
+        manager: &ResourceManager,
+        inputs: &[(&str, usize)],
+        outputs: &[(&str, usize)],
+    ) -> Recipe {
+        let components = |components: &[(&str, usize)]| {
+            components
+                .iter()
+                .map(|&(name, quantity)| {
+                    RecipeComponent::new(manager.resource_id_by_name(name).unwrap(), quantity)
+                })
+                .collect()
+        };
+        Recipe::new(components(inputs), components(outputs), 4000, vec![])
+    }
+
+    #[test]
+    fn base_cost_expands_to_base_resources() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron", "Iron Ingot")],
+        );
+        loader.insert_recipes(
+            "press".to_string(),
+            vec![recipe_with(
+                &manager,
+                &[("Iron Ingot", 3)],
+                &[("Iron Plate", 2)],
+            )],
+        );
+        loader.insert_recipes(
+            "draw".to_string(),
+            vec![recipe_with(
+                &manager,
+                &[("Iron Plate", 1), ("Copper Ingot", 1)],
+                &[("Iron Wire", 2)],
+            )],
+        );
+
+        let single = |name, amount| {
+            vec![(id(name), amount)]
+                .into_iter()
+                .collect::<HashMap<_, _>>()
+        };
+        assert_eq!(loader.total_base_cost(id("Iron Ingot")), single("Iron", 1));
+        // A press makes two plates from three ingots, so one plate costs a whole press
+        assert_eq!(loader.total_base_cost(id("Iron Plate")), single("Iron", 3));
+        assert_eq!(
+            loader.total_base_cost(id("Iron Wire")),
+            vec![(id("Iron"), 3), (id("Copper Ingot"), 1)]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(loader.total_base_cost(id("Iron")), single("Iron", 1));
+    }
+
+    #[test]
+    fn base_cost_shares_crafts_between_uses() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "smelt".to_string(),
+            vec![recipe_with(&manager, &[("Iron", 1)], &[("Iron Ingot", 2)])],
+        );
+        loader.insert_recipes(
+            "press".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+        loader.insert_recipes(
+            "draw".to_string(),
+            vec![recipe_with(
+                &manager,
+                &[("Iron Ingot", 1), ("Iron Plate", 1)],
+                &[("Iron Wire", 1)],
+            )],
+        );
+        // The ingot drawn and the ingot pressed come from the same smelt
+        assert_eq!(
+            loader.total_base_cost(id("Iron Wire")),
+            vec![(id("Iron"), 1)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn base_cost_counts_output_chances() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "smelt".to_string(),
+            vec![Recipe::new(
+                vec![RecipeComponent::new(id("Iron"), 1)],
+                vec![RecipeComponent::new(id("Iron Ingot"), 1).with_chance(0.5)],
+                4000,
+                vec![],
+            )],
+        );
+        // Half the smelts make an ingot, so an ingot takes two on average
+        assert_eq!(
+            loader.total_base_cost(id("Iron Ingot")),
+            vec![(id("Iron"), 2)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn bottleneck_is_the_scarcest_input() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let coal = manager
+            .add_resource(Resource::new(
+                "Coal",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[],
+            ))
+            .unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "smelt".to_string(),
+            vec![recipe_with(
+                &manager,
+                &[("Iron Ore", 2), ("Coal", 1)],
+                &[("Iron Ingot", 1)],
+            )],
+        );
+
+        let mut available = Inventory::new();
+        available.set(id("Iron Ore"), 10);
+        available.set(coal, 100);
+        assert_eq!(
+            loader.bottleneck(id("Iron Ingot"), &available),
+            Some(id("Iron Ore"))
+        );
+        available.set(coal, 4);
+        assert_eq!(loader.bottleneck(id("Iron Ingot"), &available), Some(coal));
+        available.set(id("Iron Ore"), 0);
+        assert_eq!(
+            loader.bottleneck(id("Iron Ingot"), &available),
+            Some(id("Iron Ore"))
+        );
+    }
+
+    #[test]
+    fn base_cost_stops_at_cycles() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.insert_recipes(
+            "melt".to_string(),
+            vec![recipe_between(&manager, "Iron Plate", "Iron Ingot")],
+        );
+        loader.insert_recipes(
+            "press".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+        assert_eq!(
+            loader.total_base_cost(id("Iron Plate")),
+            vec![(id("Iron Plate"), 1)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn identical_recipes_are_duplicates() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")],
+        );
+        loader.created_recipes.insert(
+            "furnace".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")],
+        );
+        loader.created_recipes.insert(
+            "compress".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+        assert_eq!(
+            loader.duplicate_recipes(),
+            vec![("furnace".to_string(), "smelt".to_string())]
+        );
+    }
+
+    #[test]
+    fn overlapping_inputs_and_outputs_are_flagged() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")],
+        );
+        assert!(loader.diagnostics().is_empty());
+
+        loader.created_recipes.insert(
+            "polish".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Ingot")],
+        );
+        let expected = Diagnostic {
+            severity: Severity::Warning,
+            message: "Recipe polish uses Iron Ingot as both an input and an output".to_string(),
+        };
+        assert_eq!(loader.diagnostics(), vec![expected.clone()]);
+        assert!(loader.validate().is_empty());
+
+        loader.set_overlap_severity(Severity::Error);
+        assert_eq!(
+            loader.validate(),
+            vec![RecipeError::Conflict(expected.message)]
+        );
+    }
+
+    #[test]
+    fn tree_json_expands_to_base() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")],
+        );
+        loader.created_recipes.insert(
+            "compress".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+
+        let tree = loader.tree_json(id("Iron Plate")).unwrap();
+        assert_eq!(
+            tree,
+            json!({
+                "id": id("Iron Plate"),
+                "name": "Iron Plate",
+                "recipe": "compress",
+                "inputs": [{
+                    "id": id("Iron Ingot"),
+                    "name": "Iron Ingot",
+                    "quantity": 1,
+                    "recipe": "smelt",
+                    "inputs": [{
+                        "id": id("Iron Ore"),
+                        "name": "Iron Ore",
+                        "quantity": 1
+                    }]
+                }]
+            })
+        );
+
+        loader.created_recipes.insert(
+            "unsmelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Ore")],
+        );
+        assert_eq!(
+            loader.tree_json(id("Iron Plate")),
+            Err(ResourceError::RecipeCycle(vec![
+                "smelt".to_string(),
+                "unsmelt".to_string()
+            ]))
+        );
+        assert_eq!(
+            loader.tree_json(u64::MAX),
+            Err(ResourceError::NotFound(format!("#{}", u64::MAX)))
+        );
+    }
+
+    #[test]
+    fn produced_base_resources_are_flagged() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ore", "Iron Ingot")],
+        );
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let base = [id("Iron Ore"), id("Copper Ore")]
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        assert!(loader.validate_base_resources(&base).is_empty());
+
+        loader.created_recipes.insert(
+            "transmute".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Copper Ore")],
+        );
+        assert_eq!(
+            loader.validate_base_resources(&base),
+            vec![id("Copper Ore")]
+        );
+    }
 
+    struct MemorySource(&'static str);
+
+    impl RecipeSource for MemorySource {
+        fn load(&self) -> Result<HashMap<String, RecipePattern>, Box<dyn Error>> {
+            Ok(serde_json::from_str(self.0)?)
+        }
+    }
+
+    #[test]
+    fn loads_from_memory_source() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let source = MemorySource(
+            r#"{
+                "smelt": {
+                    "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                    "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                }
+            }"#,
+        );
+        let mut loader = RecipeLoader::with_source(source, &manager);
+        loader.load_recipes().unwrap();
+        assert_eq!(
+            loader.created_recipes["smelt"][0],
+            recipe_between(&manager, "Iron Ore", "Iron Ingot")
+        );
+    }
+
+    #[test]
+    fn loads_from_reader() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let recipes = r#"{
+            "smelt": {
+                "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }
+        }"#;
+        let mut loader = RecipeLoader::new("missing.json", &manager);
+        let summary = loader.load_recipes_from_reader(recipes.as_bytes()).unwrap();
+        assert_eq!(summary.files, 0);
+        assert_eq!(summary.recipes, 1);
+        assert_eq!(
+            loader.created_recipes["smelt"][0],
+            recipe_between(&manager, "Iron Ore", "Iron Ingot")
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_recipes_match_json() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let json = r#"{
+            "smelt": {
+                "input": [{ "resource": { "tags": ["Base", "Metal"] }, "quantity": 1 }],
+                "output": [{ "resource": "{input0} Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": ["Building: Furnace"]
+            }
+        }"#;
+        let yaml = r#"
+# Any base metal can be smelted
+smelt:
+  input:
+    - resource:
+        tags: [Base, Metal]
+      quantity: 1
+  output:
+    - resource: "{input0} Ingot"
+      quantity: 1
+  base_time: 4000
+  requirements:
+    - "Building: Furnace"
+"#;
+        let dir = TempDir::new("recipe_formats");
+        let json_path = dir.join("recipes.json");
+        let yaml_path = dir.join("recipes.yml");
+        std::fs::write(&json_path, json).unwrap();
+        std::fs::write(&yaml_path, yaml).unwrap();
+
+        let mut from_json = RecipeLoader::new(&json_path, &manager);
+        let mut from_yaml = RecipeLoader::new(&yaml_path, &manager);
+        let json_result = from_json.load_recipes();
+        let yaml_result = from_yaml.load_recipes();
+        std::fs::remove_file(&json_path).unwrap();
+        std::fs::remove_file(&yaml_path).unwrap();
+        json_result.unwrap();
+        yaml_result.unwrap();
+        assert_eq!(from_yaml.created_recipes["smelt"].len(), 3);
+        assert_eq!(from_yaml.created_recipes, from_json.created_recipes);
+    }
+
+    #[test]
+    fn failed_reload_keeps_recipes() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let dir = TempDir::new("reload");
+        let path = dir.join("recipes.json");
+        std::fs::write(&path, SMELT_FILE).unwrap();
+        let mut loader = RecipeLoader::new(&path, &manager);
+        loader.load_recipes().unwrap();
+
+        std::fs::write(&path, PRESS_FILE).unwrap();
+        loader.reload().unwrap();
+        assert!(!loader.created_recipes.contains_key("smelt"));
+
+        std::fs::write(&path, "{ \"press\": ").unwrap();
+        let result = loader.reload();
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+        assert_eq!(loader.created_recipes.len(), 1);
+        assert_eq!(loader.created_recipes["press"].len(), 1);
+    }
+
+    #[cfg(feature = "hot-reload")]
+    #[test]
+    fn watched_file_is_reloaded() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let dir = TempDir::new("watched");
+        let path = dir.join("recipes.json");
+        std::fs::write(&path, SMELT_FILE).unwrap();
+        let mut loader = RecipeLoader::new(&path, &manager);
+        loader.load_recipes().unwrap();
+        let watcher = loader.watch().unwrap();
+
+        assert!(!loader.poll_reload(&watcher, |_| panic!("nothing changed")));
+
+        std::fs::write(&path, PRESS_FILE).unwrap();
+        assert_eq!(poll_until_reloaded(&mut loader, &watcher), Ok(()));
+        assert!(loader.created_recipes.contains_key("press"));
+        assert!(!loader.created_recipes.contains_key("smelt"));
+
+        std::fs::write(&path, "{ \"press\": ").unwrap();
+        assert!(matches!(
+            poll_until_reloaded(&mut loader, &watcher),
+            Err(RecipeError::Unloadable(_))
+        ));
+        assert!(loader.created_recipes.contains_key("press"));
+    }
+
+    /// Polls the watcher until it reloads the recipes, giving the result the callback was given
+    #[cfg(feature = "hot-reload")]
+    fn poll_until_reloaded(
+        loader: &mut RecipeLoader,
+        watcher: &RecipeWatcher,
+    ) -> Result<(), RecipeError> {
+        let outcome = std::cell::RefCell::new(None);
+        for _ in 0..200 {
+            if loader.poll_reload(watcher, |result| {
+                *outcome.borrow_mut() = Some(result.clone())
+            }) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        outcome.into_inner().expect("the change was never seen")
+    }
+
+    #[test]
+    fn every_expanded_recipe_is_kept() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let source = MemorySource(
+            r#"{
+                "smelt": {
+                    "input": [{ "resource": { "tags": ["Base", "Metal"] }, "quantity": 1 }],
+                    "output": [{ "resource": "{input0} Ingot", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                }
+            }"#,
+        );
+        let mut loader = RecipeLoader::with_source(source, &manager);
+        let summary = loader.load_recipes().unwrap();
+        assert_eq!(summary.recipes, 3);
+        assert_eq!(loader.created_recipes["smelt"].len(), 3);
+        for metal in &["Iron", "Copper", "Gold"] {
+            let ingot = manager
+                .resource_id_by_name(format!("{} Ingot", metal))
+                .unwrap();
+            assert_eq!(
+                loader.recipes_producing(ingot),
+                vec![&recipe_between(
+                    &manager,
+                    metal,
+                    &format!("{} Ingot", metal)
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn saved_recipes_read_back() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("recipes.json", &manager);
+        loader
+            .created_recipes
+            .insert("smelt".to_string(), vec![smelt_recipe(&manager, 4000)]);
+        loader.created_recipes.insert(
+            "wire".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Wire")],
+        );
+
+        let dir = TempDir::new("saved_recipes");
+        let path = dir.join("recipes.json");
+        loader.save_compiled(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let read: HashMap<RecipeKey, Vec<Recipe>> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(read, loader.created_recipes);
+        assert!(saved.find("\"smelt\"").unwrap() < saved.find("\"wire\"").unwrap());
+    }
+
+    #[test]
+    fn producers_and_consumers_follow_reloads() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let mut loader = RecipeLoader::with_source(
+            MemorySource(
+                r#"{
+                    "smelt": {
+                        "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                        "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                        "base_time": 4000,
+                        "requirements": []
+                    },
+                    "press": {
+                        "input": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                        "output": [{ "resource": "Iron Plate", "quantity": 1 }],
+                        "base_time": 4000,
+                        "requirements": []
+                    },
+                    "draw": {
+                        "input": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                        "output": [{ "resource": "Iron Wire", "quantity": 2 }],
+                        "base_time": 4000,
+                        "requirements": []
+                    }
+                }"#,
+            ),
+            &manager,
+        );
+        loader.load_recipes().unwrap();
+        assert_eq!(
+            loader.recipes_producing(id("Iron Ingot")),
+            vec![&loader.created_recipes["smelt"][0]]
+        );
+        assert_eq!(
+            loader.recipes_consuming(id("Iron Ingot")),
+            vec![
+                &loader.created_recipes["draw"][0],
+                &loader.created_recipes["press"][0]
+            ]
+        );
+        assert!(loader.recipes_producing(id("Iron Ore")).is_empty());
+
+        // Wire is now drawn from plates
+        loader.source = MemorySource(
+            r#"{
+                "smelt": {
+                    "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                    "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                },
+                "press": {
+                    "input": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                    "output": [{ "resource": "Iron Plate", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                },
+                "draw": {
+                    "input": [{ "resource": "Iron Plate", "quantity": 1 }],
+                    "output": [{ "resource": "Iron Wire", "quantity": 2 }],
+                    "base_time": 4000,
+                    "requirements": []
+                }
+            }"#,
+        );
+        loader.reload().unwrap();
+        assert_eq!(
+            loader.recipes_consuming(id("Iron Ingot")),
+            vec![&loader.created_recipes["press"][0]]
+        );
+        assert_eq!(
+            loader.recipes_consuming(id("Iron Plate")),
+            vec![&loader.created_recipes["draw"][0]]
+        );
+        assert_eq!(loader.recipes_producing(id("Iron Wire")).len(), 1);
+    }
+
+    const SMELT_FILE: &str = r#"{
+        "smelt": {
+            "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+            "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+            "base_time": 4000,
+            "requirements": []
+        }
+    }"#;
+
+    const PRESS_FILE: &str = r#"{
+        "press": {
+            "input": [{ "resource": "Iron Ingot", "quantity": 1 }],
+            "output": [{ "resource": "Iron Plate", "quantity": 1 }],
+            "base_time": 4000,
+            "requirements": []
+        }
+    }"#;
+
+    #[test]
+    fn loads_every_file_in_directory() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let dir = TempDir::new("recipe_directory");
+        std::fs::write(dir.join("smelting.json"), SMELT_FILE).unwrap();
+        std::fs::write(dir.join("pressing.json"), PRESS_FILE).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not recipes").unwrap();
+
+        let mut loader = RecipeLoader::from_directory(dir.path(), &manager);
+        let summary = loader.load_all().unwrap();
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.recipes, 2);
+        assert_eq!(
+            loader.created_recipes["press"][0],
+            recipe_between(&manager, "Iron Ingot", "Iron Plate")
+        );
+
+        std::fs::write(dir.join("more_smelting.json"), SMELT_FILE).unwrap();
+        let error = loader.load_all().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RecipeError>(),
+            Some(&RecipeError::DuplicateKey(
+                "smelt".to_string(),
+                dir.join("smelting.json")
+            ))
+        );
+    }
+
+    #[test]
+    fn unknown_resource_fails_the_load() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let source = MemorySource(
+            r#"{
+                "smelt": {
+                    "input": [{ "resource": "Unobtainium Ore", "quantity": 1 }],
+                    "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                }
+            }"#,
+        );
+        let mut loader = RecipeLoader::with_source(source, &manager);
+        let error = loader.load_recipes().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RecipeError>(),
+            Some(&RecipeError::UnknownResource("Unobtainium Ore".to_string()))
+        );
+        assert!(loader.created_recipes.is_empty());
+    }
+
+    #[test]
+    fn coal_enables_smelting() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let coal = manager
+            .add_resource(Resource::new(
+                "Coal",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[],
+            ))
+            .unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![Recipe::new(
+                vec![
+                    RecipeComponent::new(id("Iron Ore"), 1),
+                    RecipeComponent::new(coal, 1),
+                ],
+                vec![RecipeComponent::new(id("Iron Ingot"), 1)],
+                4000,
+                vec![],
+            )],
+        );
+        loader.created_recipes.insert(
+            "compress".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Plate")],
+        );
+
+        let ore = [id("Iron Ore")].iter().copied().collect::<HashSet<_>>();
+        assert_eq!(
+            loader.recipes_enabled_by(coal, &ore),
+            vec!["smelt".to_string()]
+        );
+        assert!(loader.recipes_enabled_by(coal, &HashSet::new()).is_empty());
+        let both = [id("Iron Ore"), coal]
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        assert!(loader.recipes_enabled_by(coal, &both).is_empty());
+    }
+
+    #[test]
+    fn load_summary_counts() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let dir = TempDir::new("load_summary");
+        let path = dir.join("recipes.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "smelt iron": {
+                    "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                    "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                },
+                "smelt copper": {
+                    "input": [{ "resource": "Copper Ore", "quantity": 1 }],
+                    "output": [{ "resource": "Copper Ingot", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut loader = RecipeLoader::new(&path, &manager);
+
+        let summary = loader.load_recipes().unwrap();
+        assert_eq!(summary.files, 1);
+        assert_eq!(summary.patterns, 2);
+        assert_eq!(summary.recipes, 2);
+        assert!(summary.warnings.is_empty());
+
+        let summary = loader.reload().unwrap();
+        assert!(summary.warnings.is_empty());
+        assert_eq!(
+            summary.to_string(),
+            "loaded 2 recipes from 1 files with 0 warnings"
+        );
+
+        let smelt_iron = r#"{
+            "smelt iron": {
+                "input": [{ "resource": "Iron Ore", "quantity": 1 }],
+                "output": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                "base_time": 4000,
+                "requirements": []
+            }
+        }"#;
+        let summary = loader
+            .load_recipes_from_reader(smelt_iron.as_bytes())
+            .unwrap();
+        assert_eq!(
+            summary.warnings,
+            vec!["Recipe smelt iron is identical to an already loaded recipe".to_string()]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loading_a_key_with_other_recipes_is_an_error() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let dir = TempDir::new("duplicate_load");
+        let smelt_path = dir.join("smelt.json");
+        let both_path = dir.join("both.json");
+        std::fs::write(&smelt_path, SMELT_FILE).unwrap();
+        std::fs::write(
+            &both_path,
+            r#"{
+                "smelt": {
+                    "input": [{ "resource": "Copper Ore", "quantity": 1 }],
+                    "output": [{ "resource": "Copper Ingot", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                },
+                "press": {
+                    "input": [{ "resource": "Iron Ingot", "quantity": 1 }],
+                    "output": [{ "resource": "Iron Plate", "quantity": 1 }],
+                    "base_time": 4000,
+                    "requirements": []
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut loader = RecipeLoader::new(&smelt_path, &manager);
+        loader.load_recipes().unwrap();
+
+        loader.source = both_path.clone();
+        let error = loader.load_recipes().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RecipeError>(),
+            Some(&RecipeError::DuplicateKey(
+                "smelt".to_string(),
+                both_path.clone()
+            ))
+        );
+        assert_eq!(loader.created_recipes.len(), 1);
+        assert_eq!(
+            loader.created_recipes["smelt"][0],
+            recipe_between(&manager, "Iron Ore", "Iron Ingot")
+        );
+
+        // The same recipes under the same key aren't a collision
+        loader
+            .load_recipes_from_reader(SMELT_FILE.as_bytes())
+            .unwrap();
+        let error = loader
+            .load_recipes_from_reader(std::fs::File::open(&both_path).unwrap())
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Recipe smelt is already loaded");
+    }
+
+    #[test]
+    fn derived_values_follow_recipe_ratios() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut loader = RecipeLoader::new("", &manager);
+        loader.created_recipes.insert(
+            "smelt".to_string(),
+            vec![
+                recipe_with(&manager, &[("Iron Ore", 2)], &[("Iron Ingot", 1)]),
+                recipe_with(&manager, &[("Iron Ore", 3)], &[("Iron Ingot", 1)]),
+            ],
+        );
+        loader.created_recipes.insert(
+            "compress".to_string(),
+            vec![recipe_with(
+                &manager,
+                &[("Iron Ingot", 3)],
+                &[("Iron Plate", 2)],
+            )],
+        );
+
+        // The loader borrows `manager`, so the values go into an identical one
+        let mut values = ResourceManager::new();
+        setup_resource_manager(&mut values).unwrap();
+        let id = |name| values.resource_id_by_name(name).unwrap();
+        let ore = id("Iron Ore");
+        values
+            .update_resource(ore, |res| *res = res.clone().with_base_value(1.5))
+            .unwrap();
+        loader.compute_derived_values(&mut values).unwrap();
+
+        let value = |name| values.resource_by_name(name).unwrap().base_value();
+        assert_eq!(value("Iron Ore"), 1.5);
+        assert_eq!(value("Iron Ingot"), 1.5 * 2.0);
+        assert_eq!(value("Iron Plate"), 1.5 * 2.0 * 3.0 / 2.0);
+
+        loader.created_recipes.insert(
+            "unsmelt".to_string(),
+            vec![recipe_between(&manager, "Iron Ingot", "Iron Ore")],
+        );
+        assert_eq!(
+            loader.compute_derived_values(&mut values),
+            Err(ResourceError::RecipeCycle(vec![
+                "smelt".to_string(),
+                "unsmelt".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn combined_file_loads_resources_then_recipes() {
+        let dir = TempDir::new("combined");
+        let path = dir.join("pack.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "resources": [
+                    { "name": "Tin Ore", "tags": ["Ore"] },
+                    { "name": "Tin Ingot", "tags": ["Metal", "Ingot"], "base_value": 2.0 }
+                ],
+                "recipes": {
+                    "smelt tin": {
+                        "input": [{ "resource": "Tin Ore", "quantity": 2 }],
+                        "output": [{ "resource": "Tin Ingot", "quantity": 1 }],
+                        "base_time": 4000,
+                        "requirements": []
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let mut manager = ResourceManager::new();
+        let (loader, summary) = load_all(&path, &mut manager).unwrap();
+        assert_eq!(summary.recipes, 1);
+        let resources = loader.resource_manager();
+        let ore = resources.resource_id_by_name("Tin Ore").unwrap();
+        let ingot = resources.resource_by_name("Tin Ingot").unwrap();
+        assert_eq!(ingot.base_value(), 2.0);
+        assert_eq!(
+            loader.recipes_producing(ingot.id()),
+            vec![&Recipe::new(
+                vec![RecipeComponent::new(ore, 2)],
+                vec![RecipeComponent::new(ingot.id(), 1)],
+                4000,
+                vec![]
+            )]
+        );
+    }
+}
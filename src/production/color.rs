@@ -0,0 +1,205 @@
+use crate::production::resource::{stable_name_hash, Resource, ResourceTag};
+use iced::Color;
+
+/// The foreground color of a resource that hasn't been given colors
+pub const DEFAULT_FG_COLOR: Color = Color::WHITE;
+/// The background color of a resource that hasn't been given colors
+pub const DEFAULT_BG_COLOR: Color = Color::BLACK;
+
+/// How far, in degrees, a resource's hue may stray from its family's hue
+const HUE_VARIATION: f32 = 20.0;
+
+/// Generates resource colors from a family hue picked by the resource's most specific tag, varied
+/// by a hash of its name. The hash is stable, so a resource gets the same colors on every run.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ColorScheme {
+    /// Light text on a dark background
+    Dark,
+    /// Dark text on a light background
+    Light,
+}
+
+impl ColorScheme {
+    /// The hue, in degrees, of the family of resources whose most specific tag is `tag`
+    pub fn family_hue(tag: Option<&ResourceTag>) -> f32 {
+        match tag {
+            Some(ResourceTag::Ore) => 25.0,
+            Some(ResourceTag::Ingot) => 210.0,
+            Some(ResourceTag::Metal) => 180.0,
+            Some(ResourceTag::Base) => 120.0,
+            Some(ResourceTag::Custom(_)) | None => 280.0,
+        }
+    }
+
+    /// Gets the (foreground, background) colors for a resource
+    pub fn colors_for(&self, resource: &Resource) -> (Color, Color) {
+        let variation = (stable_name_hash(resource.name()) % 1000) as f32 / 999.0 * 2.0 - 1.0;
+        let hue = Self::family_hue(primary_tag(resource).as_ref()) + variation * HUE_VARIATION;
+        match self {
+            ColorScheme::Dark => (hsl(hue, 0.5, 0.85), hsl(hue, 0.5, 0.25)),
+            ColorScheme::Light => (hsl(hue, 0.5, 0.2), hsl(hue, 0.5, 0.85)),
+        }
+    }
+}
+
+/// The most specific tag of a resource, which decides its color family
+pub fn primary_tag(resource: &Resource) -> Option<ResourceTag> {
+    [
+        ResourceTag::Ore,
+        ResourceTag::Ingot,
+        ResourceTag::Metal,
+        ResourceTag::Base,
+    ]
+    .iter()
+    .find(|tag| resource.contains_tag(tag))
+    .cloned()
+}
+
+/// Converts a hue in degrees, and saturation and lightness in `[0, 1]`, to an opaque color
+pub fn hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    Color::from_rgb(r + m, g + m, b + m)
+}
+
+/// Writes a color as a `"#RRGGBB"` hex string, with an alpha byte appended if it isn't opaque
+pub fn to_hex(color: &Color) -> String {
+    let byte = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut hex = format!(
+        "#{:02X}{:02X}{:02X}",
+        byte(color.r),
+        byte(color.g),
+        byte(color.b)
+    );
+    if color.a < 1.0 {
+        hex.push_str(&format!("{:02X}", byte(color.a)));
+    }
+    hex
+}
+
+/// Parses a `"#RRGGBB"` or `"#RRGGBBAA"` hex string
+pub fn from_hex(hex: &str) -> Option<Color> {
+    let digits = hex.strip_prefix('#')?;
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+        return None;
+    }
+    let channel = |index: usize| {
+        u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16)
+            .ok()
+            .map(|byte| byte as f32 / 255.0)
+    };
+    let a = if digits.len() == 8 { channel(3)? } else { 1.0 };
+    Some(Color {
+        r: channel(0)?,
+        g: channel(1)?,
+        b: channel(2)?,
+        a,
+    })
+}
+
+/// Serde support for colors, for use with `#[serde(with = "...")]`. Colors are written as hex
+/// strings, and read from either a hex string or an object of `r`, `g`, `b` and optionally `a`
+/// channels between 0 and 1. Channels outside that range are clamped into it.
+pub mod serde_color {
+    use super::{from_hex, to_hex};
+    use iced::Color;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    fn opaque() -> f32 {
+        1.0
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ColorRepr {
+        Hex(String),
+        Channels {
+            r: f32,
+            g: f32,
+            b: f32,
+            #[serde(default = "opaque")]
+            a: f32,
+        },
+    }
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        match ColorRepr::deserialize(deserializer)? {
+            ColorRepr::Hex(hex) => from_hex(&hex)
+                .ok_or_else(|| D::Error::custom(format!("{} is not a hex color", hex))),
+            ColorRepr::Channels { r, g, b, a } => {
+                let clamp = |channel: f32| channel.clamp(0.0, 1.0);
+                Ok(Color {
+                    r: clamp(r),
+                    g: clamp(g),
+                    b: clamp(b),
+                    a: clamp(a),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hsl_primaries() {
+        assert_eq!(hsl(0.0, 1.0, 0.5), Color::from_rgb(1.0, 0.0, 0.0));
+        assert_eq!(hsl(120.0, 1.0, 0.5), Color::from_rgb(0.0, 1.0, 0.0));
+        assert_eq!(hsl(240.0, 1.0, 0.5), Color::from_rgb(0.0, 0.0, 1.0));
+        assert_eq!(hsl(0.0, 0.0, 1.0), Color::WHITE);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Colored {
+        #[serde(with = "serde_color")]
+        color: Color,
+    }
+
+    fn read(json: &str) -> Result<Color, serde_json::Error> {
+        serde_json::from_str::<Colored>(json).map(|colored| colored.color)
+    }
+
+    #[test]
+    fn colors_round_trip_as_hex() {
+        let copper = from_hex("#B87333").unwrap();
+        let json = serde_json::to_string(&Colored { color: copper }).unwrap();
+        assert_eq!(json, r##"{"color":"#B87333"}"##);
+        assert_eq!(read(&json).unwrap(), copper);
+
+        let faded = read(r##"{"color":"#FF000080"}"##).unwrap();
+        assert_eq!(faded.a, 128.0 / 255.0);
+        assert_eq!(to_hex(&faded), "#FF000080");
+
+        assert!(read(r##"{"color":"#GG0000"}"##).is_err());
+        assert!(read(r##"{"color":"B87333"}"##).is_err());
+        assert!(read(r##"{"color":"#B8733"}"##).is_err());
+    }
+
+    #[test]
+    fn colors_read_from_channels() {
+        let color = read(r#"{"color":{"r":1.0,"g":0.5,"b":0.0}}"#).unwrap();
+        assert_eq!(color, Color::from_rgb(1.0, 0.5, 0.0));
+        let json = serde_json::to_string(&Colored { color }).unwrap();
+        assert_eq!(read(&json).unwrap(), from_hex("#FF8000").unwrap());
+
+        let clamped = read(r#"{"color":{"r":2.0,"g":-1.0,"b":0.25,"a":1.5}}"#).unwrap();
+        assert_eq!(clamped, Color::from_rgba(1.0, 0.0, 0.25, 1.0));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::processing::recipe::Recipe;
+use crate::production::resource::{Resource, ResourceManager};
+use std::collections::HashMap;
+
+/// How much of each resource, by id, is on hand. Resources with none on hand aren't stored, so two
+/// inventories holding the same amounts are equal.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory(HashMap<u64, usize>);
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn quantity(&self, resource_id: u64) -> usize {
+        self.0.get(&resource_id).copied().unwrap_or(0)
+    }
+
+    /// Sets how much of a resource is on hand
+    pub fn set(&mut self, resource_id: u64, quantity: usize) {
+        if quantity == 0 {
+            self.0.remove(&resource_id);
+        } else {
+            self.0.insert(resource_id, quantity);
+        }
+    }
+
+    /// Adds to how much of a resource is on hand, returning false without changing anything if
+    /// the quantity would overflow
+    pub fn add(&mut self, resource_id: u64, quantity: usize) -> bool {
+        match self.quantity(resource_id).checked_add(quantity) {
+            Some(total) => {
+                self.set(resource_id, total);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gets the ids and quantities of every resource on hand
+    pub fn iter(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        self.0.iter().map(|(&id, &quantity)| (id, quantity))
+    }
+
+    /// How much of each resource changed going from this inventory to `other`, such as a snapshot
+    /// taken after a craft. Gains are positive and losses negative, and unchanged resources are
+    /// left out.
+    pub fn diff(&self, other: &Inventory) -> HashMap<u64, i64> {
+        self.0
+            .keys()
+            .chain(other.0.keys())
+            .map(|&id| (id, other.quantity(id) as i64 - self.quantity(id) as i64))
+            .filter(|&(_, delta)| delta != 0)
+            .collect()
+    }
+
+    /// Reports how full storage is for every resource on hand, by id: the resource, how much is
+    /// held, its [stack size](Resource::stack_size) and the fraction of it that's held. Uncapped
+    /// resources have no stack size and count as empty. Resources the manager doesn't have are
+    /// left out.
+    pub fn capacity_report<'m>(
+        &self,
+        manager: &'m ResourceManager,
+    ) -> Vec<(&'m Resource, u64, Option<u32>, f64)> {
+        let mut report = self
+            .iter()
+            .filter_map(|(id, quantity)| {
+                let resource = manager.resource_by_id(id)?;
+                let held = quantity as u64;
+                let fraction = match resource.stack_size() {
+                    Some(cap) => held as f64 / cap as f64,
+                    None => 0.0,
+                };
+                Some((resource, held, resource.stack_size(), fraction))
+            })
+            .collect::<Vec<_>>();
+        report.sort_by_key(|(resource, ..)| resource.id());
+        report
+    }
+
+    /// Gets the capped resources on hand that fill more than `threshold` of their stack size, by
+    /// id
+    pub fn near_full<'m>(&self, manager: &'m ResourceManager, threshold: f64) -> Vec<&'m Resource> {
+        self.capacity_report(manager)
+            .into_iter()
+            .filter(|&(_, _, cap, fraction)| cap.is_some() && fraction > threshold)
+            .map(|(resource, ..)| resource)
+            .collect()
+    }
+
+    /// Works out what crafting a recipe would do to the inventory, without changing it. The
+    /// recipe's `any_of` slots are filled as [`Recipe::inputs_for`] describes. Outputs with less
+    /// than a certain chance aren't counted, as a craft may not produce them.
+    pub fn preview_craft(&self, recipe: &Recipe) -> CraftPreview {
+        let mut preview = CraftPreview {
+            craftable: recipe.can_craft(self),
+            ..CraftPreview::default()
+        };
+        for input in recipe.inputs_for(self) {
+            *preview.consumed.entry(input.resource_id()).or_insert(0) += input.quantity();
+        }
+        for catalyst in recipe.catalysts() {
+            *preview.catalysts.entry(catalyst.resource_id()).or_insert(0) += catalyst.quantity();
+        }
+        for output in recipe
+            .outputs()
+            .iter()
+            .filter(|output| output.chance() >= 1.0)
+        {
+            *preview.produced.entry(output.resource_id()).or_insert(0) += output.quantity();
+        }
+        let touched = preview
+            .consumed
+            .keys()
+            .chain(preview.catalysts.keys())
+            .chain(preview.produced.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        for id in touched {
+            let produced = preview.produced.get(&id).copied().unwrap_or(0) as i64;
+            let consumed = preview.consumed.get(&id).copied().unwrap_or(0) as i64;
+            let delta = produced - consumed;
+            if delta != 0 {
+                preview.deltas.insert(id, delta);
+            }
+            preview
+                .balances
+                .insert(id, self.quantity(id) as i64 + delta);
+        }
+        preview
+    }
+}
+
+/// What crafting a recipe would do to an inventory, as worked out by
+/// [`Inventory::preview_craft`]. Quantities are keyed by resource id.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CraftPreview {
+    /// Whether the inventory holds enough of every input and catalyst to craft
+    pub craftable: bool,
+    /// The inputs the craft would use up
+    pub consumed: HashMap<u64, usize>,
+    /// The catalysts that must be on hand, which the craft leaves in the inventory
+    pub catalysts: HashMap<u64, usize>,
+    /// The outputs the craft is certain to produce
+    pub produced: HashMap<u64, usize>,
+    /// How much each resource on hand would change by. Resources which wouldn't change, such as
+    /// catalysts, are left out.
+    pub deltas: HashMap<u64, i64>,
+    /// How much of each resource the recipe uses would be on hand afterwards. A negative balance
+    /// is how much of an input is missing.
+    pub balances: HashMap<u64, i64>,
+}
+
+impl From<HashMap<u64, usize>> for Inventory {
+    fn from(quantities: HashMap<u64, usize>) -> Self {
+        let mut inventory = Inventory::new();
+        for (id, quantity) in quantities {
+            inventory.set(id, quantity);
+        }
+        inventory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::recipe::RecipeComponent;
+    use crate::production::resource::ResourceBuilder;
+
+    #[test]
+    fn empty_quantities_are_not_stored() {
+        let mut inventory = Inventory::new();
+        inventory.set(3, 5);
+        inventory.set(3, 0);
+        assert_eq!(inventory, Inventory::new());
+
+        assert!(inventory.add(1, usize::MAX));
+        assert!(!inventory.add(1, 1));
+        assert_eq!(inventory.quantity(1), usize::MAX);
+        assert_eq!(inventory.quantity(2), 0);
+    }
+
+    #[test]
+    fn diff_shows_what_a_craft_changed() {
+        let (ore, coal, ingot) = (1, 2, 3);
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(ore, 2), RecipeComponent::new(coal, 1)],
+            vec![RecipeComponent::new(ingot, 1)],
+            1000,
+            vec![],
+        );
+        let mut inventory = Inventory::new();
+        inventory.set(ore, 2);
+        inventory.set(coal, 3);
+        let before = inventory.clone();
+        recipe.craft(&mut inventory).unwrap();
+
+        assert_eq!(
+            before.diff(&inventory),
+            vec![(ore, -2), (coal, -1), (ingot, 1)]
+                .into_iter()
+                .collect()
+        );
+        assert!(inventory.diff(&inventory).is_empty());
+    }
+
+    #[test]
+    fn preview_leaves_the_inventory_untouched() {
+        let (ore, coal, ingot, hammer) = (1, 2, 3, 4);
+        let recipe = Recipe::new(
+            vec![RecipeComponent::new(ore, 3), RecipeComponent::new(coal, 1)],
+            vec![RecipeComponent::new(ingot, 1)],
+            1000,
+            vec![],
+        )
+        .with_catalysts(vec![RecipeComponent::new(hammer, 1)]);
+        let inventory = Inventory::from(
+            vec![(ore, 5), (coal, 2), (hammer, 1)]
+                .into_iter()
+                .collect::<HashMap<_, _>>(),
+        );
+        let before = inventory.clone();
+
+        let preview = inventory.preview_craft(&recipe);
+        assert!(preview.craftable);
+        assert_eq!(
+            preview.consumed,
+            vec![(ore, 3), (coal, 1)].into_iter().collect()
+        );
+        assert_eq!(preview.catalysts, vec![(hammer, 1)].into_iter().collect());
+        assert_eq!(preview.produced, vec![(ingot, 1)].into_iter().collect());
+        assert_eq!(
+            preview.deltas,
+            vec![(ore, -3), (coal, -1), (ingot, 1)]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            preview.balances,
+            vec![(ore, 2), (coal, 1), (ingot, 1), (hammer, 1)]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(inventory, before);
+
+        let mut short = inventory;
+        short.set(ore, 1);
+        let preview = short.preview_craft(&recipe);
+        assert!(!preview.craftable);
+        assert_eq!(preview.balances[&ore], -2);
+    }
+
+    #[test]
+    fn capacity_report_flags_near_full_stacks() {
+        let mut manager = ResourceManager::new();
+        let mut add = |name: &str, stack_size: Option<u32>| {
+            let builder = ResourceBuilder::new().name(name);
+            let builder = match stack_size {
+                Some(stack_size) => builder.stack_size(stack_size),
+                None => builder,
+            };
+            manager.add_resource(builder.build().unwrap()).unwrap()
+        };
+        let (ore, coal, sand) = (
+            add("Ore", Some(100)),
+            add("Coal", Some(50)),
+            add("Sand", None),
+        );
+        let mut inventory = Inventory::new();
+        inventory.set(ore, 90);
+        inventory.set(coal, 10);
+        inventory.set(sand, 1000);
+
+        let report = inventory
+            .capacity_report(&manager)
+            .into_iter()
+            .map(|(resource, held, cap, fraction)| (resource.id(), held, cap, fraction))
+            .collect::<Vec<_>>();
+        let mut expected = vec![
+            (ore, 90, Some(100), 0.9),
+            (coal, 10, Some(50), 0.2),
+            (sand, 1000, None, 0.0),
+        ];
+        expected.sort_by_key(|&(id, ..)| id);
+        assert_eq!(report, expected);
+
+        let near_full = inventory.near_full(&manager, 0.8);
+        assert_eq!(near_full.len(), 1);
+        assert_eq!(near_full[0].id(), ore);
+        assert!(inventory.near_full(&manager, 0.9).is_empty());
+    }
+}
@@ -1 +1,7 @@
+pub mod color;
+pub mod inventory;
+pub mod regex_cache;
 pub mod resource;
+pub mod transformer;
+pub mod trie;
+pub mod upgrades;
@@ -0,0 +1,130 @@
+use regex::Regex;
+use std::collections::HashMap;
+
+/// How many patterns a [`RegexCache`] keeps unless given another capacity
+pub const DEFAULT_REGEX_CACHE_CAPACITY: usize = 32;
+
+/// Keeps compiled regular expressions by their pattern, so a pattern asked for repeatedly, such
+/// as a search box's, is only compiled once. When full, the pattern used least recently is
+/// dropped to make room.
+#[derive(Debug)]
+pub struct RegexCache {
+    capacity: usize,
+    /// Each pattern's regex, along with when it was last used
+    entries: HashMap<String, (Regex, u64)>,
+    uses: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl RegexCache {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_REGEX_CACHE_CAPACITY)
+    }
+
+    /// Creates a cache keeping at most `capacity` patterns. A capacity of 0 compiles every pattern
+    /// each time.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            uses: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Gets the regex of a pattern, compiling it if it isn't kept. Regexes share their compiled
+    /// form, so the clone given back is cheap.
+    pub fn get(&mut self, pattern: &str) -> Result<Regex, regex::Error> {
+        self.uses += 1;
+        if let Some((regex, last_used)) = self.entries.get_mut(pattern) {
+            *last_used = self.uses;
+            self.hits += 1;
+            return Ok(regex.clone());
+        }
+        self.misses += 1;
+        let regex = Regex::new(pattern)?;
+        if self.capacity > 0 {
+            self.evict_to(self.capacity - 1);
+            self.entries
+                .insert(pattern.to_string(), (regex.clone(), self.uses));
+        }
+        Ok(regex)
+    }
+
+    /// Changes how many patterns are kept, dropping the least recently used ones that no longer fit
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict_to(capacity);
+    }
+
+    /// Drops the least recently used patterns until at most `len` are kept
+    fn evict_to(&mut self, len: usize) {
+        while self.entries.len() > len {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(pattern, _)| pattern.clone())
+                .unwrap();
+            self.entries.remove(&oldest);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.entries.contains_key(pattern)
+    }
+
+    /// How many times a pattern asked for was already compiled
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// How many times a pattern asked for had to be compiled, including invalid patterns
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+impl Default for RegexCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_pattern_is_dropped() {
+        let mut cache = RegexCache::with_capacity(2);
+        cache.get("Iron").unwrap();
+        cache.get("Gold").unwrap();
+        cache.get("Iron").unwrap();
+        cache.get("Copper").unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+        assert!(cache.contains("Iron"));
+        assert!(!cache.contains("Gold"));
+        assert!(cache.contains("Copper"));
+
+        assert!(cache.get("(").is_err());
+        assert_eq!(cache.len(), 2);
+        cache.set_capacity(1);
+        assert!(!cache.contains("Iron"));
+        assert!(cache.contains("Copper"));
+    }
+}
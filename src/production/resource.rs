@@ -1,29 +1,248 @@
 use iced::Color;
-use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
-use std::iter::FromIterator;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::path::{Path, PathBuf};
-use regex::{Regex, Matches, Match, Captures};
+use regex::{Regex, Captures};
+use serde_json::Value;
+use crate::production::color::{ColorScheme, DEFAULT_BG_COLOR, DEFAULT_FG_COLOR};
+use crate::production::regex_cache::RegexCache;
+use crate::production::transformer::TransformerBuilder;
+use crate::production::trie::NameTrie;
 
-#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+/// A tag on a resource. Tags this crate doesn't know about, such as ones added by mods, are
+/// `Custom`. A custom tag is always distinct from a built-in one, even with the same name, so
+/// `Custom("Metal")` doesn't match `Metal`. Parsing and deserializing always produce the built-in
+/// tag for a built-in name.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum ResourceTag {
     Base,
     Metal,
     Ore,
-    Ingot
+    Ingot,
+    Custom(String),
 }
 
-#[derive(Debug, Clone)]
+impl Display for ResourceTag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ResourceTag::Base => "Base",
+            ResourceTag::Metal => "Metal",
+            ResourceTag::Ore => "Ore",
+            ResourceTag::Ingot => "Ingot",
+            ResourceTag::Custom(name) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Parses a tag from its name as printed by `Display`. Parsing is case-sensitive, so `"Metal"` is
+/// the built-in tag while `"metal"` is a custom one. Blank names are not tags.
+impl FromStr for ResourceTag {
+    type Err = TagParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Base" => Ok(ResourceTag::Base),
+            "Metal" => Ok(ResourceTag::Metal),
+            "Ore" => Ok(ResourceTag::Ore),
+            "Ingot" => Ok(ResourceTag::Ingot),
+            _ if s.trim().is_empty() => Err(TagParseError(s.to_string())),
+            _ => Ok(ResourceTag::Custom(s.to_string())),
+        }
+    }
+}
+
+impl Serialize for ResourceTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ResourceTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The string that failed to parse as a [`ResourceTag`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TagParseError(pub String);
+
+impl Display for TagParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not a resource tag", self.0)
+    }
+}
+
+impl Error for TagParseError {}
+
+/// How long a chain of transformers may be before it is assumed to never end, such as when a
+/// transformer creates resources that match its own predicate
+const MAX_DERIVATION_DEPTH: usize = 32;
+
+/// How many resources pair transformers look at. Every ordered pair of them is tried, so this
+/// caps each pair transformer at a little over 16000 calls when it is added or transformers are
+/// reapplied. Resources past the limit, in id order, are never paired.
+pub const MAX_PAIRED_RESOURCES: usize = 128;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ResourceError {
+    /// No resource has the given name, or the given id when formatted as `#<id>`
+    NotFound(String),
+    /// A resource with the given name already exists
+    AlreadyExists(String),
+    /// Strict validation found the given problems with a resource
+    Invalid(Vec<String>),
+    /// No more than `limit` resources may have the tag
+    TagLimitExceeded { tag: ResourceTag, limit: usize },
+    /// A chain of transformers went on too long to ever end, such as a transformer creating
+    /// resources it applies to again
+    CyclicDerivation,
+    /// The id is already used by the named resource
+    IdTaken { id: u64, name: String },
+    /// No recipes are loaded under the given key
+    UnknownRecipe(String),
+    /// The recipes under the given keys depend on each other in a cycle
+    RecipeCycle(Vec<String>),
+}
+
+impl Display for ResourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceError::NotFound(resource) => write!(f, "No resource {} exists", resource),
+            ResourceError::AlreadyExists(resource) => {
+                write!(f, "Resource {} already exists", resource)
+            }
+            ResourceError::Invalid(problems) => write!(f, "{}", problems.join("; ")),
+            ResourceError::TagLimitExceeded { tag, limit } => write!(
+                f,
+                "Tag limit exceeded: no more than {} resources may have the {:?} tag",
+                limit, tag
+            ),
+            ResourceError::CyclicDerivation => {
+                write!(f, "transformer produced a cyclic resource")
+            }
+            ResourceError::IdTaken { id, name } => {
+                write!(f, "Id {} is already used by {}", id, name)
+            }
+            ResourceError::UnknownRecipe(key) => write!(f, "No recipe {} is loaded", key),
+            ResourceError::RecipeCycle(keys) => {
+                write!(f, "Recipes form a cycle: {}", keys.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for ResourceError {}
+
+/// Why a resource's icon can't be used
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IconError {
+    /// No file exists at the path
+    Missing(PathBuf),
+    /// The file exists but couldn't be read, for the given reason
+    Unreadable(PathBuf, String),
+    /// The file isn't a PNG, JPEG, GIF or BMP image
+    UnsupportedFormat(PathBuf),
+}
+
+impl Display for IconError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IconError::Missing(path) => write!(f, "Icon {} doesn't exist", path.display()),
+            IconError::Unreadable(path, reason) => {
+                write!(f, "Icon {} couldn't be read: {}", path.display(), reason)
+            }
+            IconError::UnsupportedFormat(path) => {
+                write!(f, "Icon {} isn't a supported image", path.display())
+            }
+        }
+    }
+}
+
+impl Error for IconError {}
+
+/// The leading bytes of each supported icon format
+const IMAGE_SIGNATURES: [&[u8]; 4] = [b"\x89PNG\r\n\x1a\n", b"\xff\xd8\xff", b"GIF8", b"BM"];
+
+/// Checks that an icon file exists and starts like a supported image
+fn check_icon(path: &Path) -> Result<(), IconError> {
+    let mut header = [0; 8];
+    let read = File::open(path).and_then(|mut file| file.read(&mut header));
+    match read {
+        Ok(len)
+            if IMAGE_SIGNATURES
+                .iter()
+                .any(|sig| header[..len].starts_with(sig)) =>
+        {
+            Ok(())
+        }
+        Ok(_) => Err(IconError::UnsupportedFormat(path.to_path_buf())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(IconError::Missing(path.to_path_buf()))
+        }
+        Err(e) => Err(IconError::Unreadable(path.to_path_buf(), e.to_string())),
+    }
+}
+
+/// Which consistency checks [`Resource::validate_with`] runs
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationRules {
+    pub non_empty_name: bool,
+    pub ingot_implies_metal: bool,
+    pub distinct_colors: bool,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self {
+            non_empty_name: true,
+            ingot_implies_metal: true,
+            distinct_colors: true,
+        }
+    }
+}
+
+/// Resources are equal when every field, including the id, is equal. Colors and base values are
+/// compared exactly, so a resource with a NaN color channel or value isn't equal even to itself,
+/// despite the `Eq` impl.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Resource {
     id: u64,
     name: String,
+    symbol: Option<String>,
     description: String,
     base_icon: PathBuf,
+    #[serde(with = "crate::production::color::serde_color")]
     fg_color: Color,
+    #[serde(with = "crate::production::color::serde_color")]
     bg_color: Color,
     tags: Vec<ResourceTag>,
+    /// How advanced the resource is, starting from 0 for raw resources
+    #[serde(default)]
+    tier: u8,
+    /// What one of the resource is worth, for pricing recipes
+    #[serde(default)]
+    base_value: f64,
+    /// How much of the resource can be held at once, if that's limited
+    #[serde(default)]
+    stack_size: Option<u32>,
+    derived: bool,
+    unlocked_by_default: bool,
+    /// Metadata this crate doesn't model, such as fields kept from imported data
+    extra: HashMap<String, Value>,
 }
 
+impl Eq for Resource {}
+
 impl Resource {
     pub fn new<
         'a,
@@ -42,14 +261,54 @@ impl Resource {
         Self {
             id: 0,
             name: name.as_ref().to_string(),
+            symbol: None,
             description: description.as_ref().to_string(),
             base_icon: PathBuf::from(base_icon.as_ref()),
             fg_color,
             bg_color,
             tags: tags.into_iter().cloned().collect(),
+            tier: 0,
+            base_value: 0.0,
+            stack_size: None,
+            derived: false,
+            unlocked_by_default: true,
+            extra: HashMap::new(),
         }
     }
 
+    /// Sets a short abbreviation for the resource, such as a chemical symbol
+    pub fn with_symbol<S: AsRef<str>>(mut self, symbol: S) -> Self {
+        self.symbol = Some(symbol.as_ref().to_string());
+        self
+    }
+
+    pub fn with_tier(mut self, tier: u8) -> Self {
+        self.tier = tier;
+        self
+    }
+
+    pub fn with_base_value(mut self, base_value: f64) -> Self {
+        self.base_value = base_value;
+        self
+    }
+
+    /// Limits how much of the resource can be held at once
+    pub fn with_stack_size(mut self, stack_size: u32) -> Self {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Sets whether the resource is visible from the start, rather than hidden until discovered
+    pub fn with_unlocked_by_default(mut self, unlocked_by_default: bool) -> Self {
+        self.unlocked_by_default = unlocked_by_default;
+        self
+    }
+
+    pub fn with_extra(mut self, extra: HashMap<String, Value>) -> Self {
+        self.extra = extra;
+        self
+    }
+
     pub fn id(&self) -> u64 {
         self.id
     }
@@ -58,6 +317,10 @@ impl Resource {
         &self.name
     }
 
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
     pub fn description(&self) -> &String {
         &self.description
     }
@@ -74,10 +337,105 @@ impl Resource {
         &self.bg_color
     }
 
+    pub fn tier(&self) -> u8 {
+        self.tier
+    }
+
+    pub fn base_value(&self) -> f64 {
+        self.base_value
+    }
+
+    pub fn stack_size(&self) -> Option<u32> {
+        self.stack_size
+    }
+
     pub fn tags(&self) -> &Vec<ResourceTag> {
         &self.tags
     }
 
+    /// Adds a tag if the resource doesn't already have it. A manager indexes its resources by tag,
+    /// so a resource already in one is only retagged inside [`ResourceManager::update_resource`]
+    /// or through [`ResourceManager::resource_by_id_mut`].
+    pub fn add_tag(&mut self, tag: ResourceTag) {
+        if !self.contains_tag(&tag) {
+            self.tags.push(tag);
+        }
+    }
+
+    /// Removes a tag, returning whether the resource had it. As with [`Resource::add_tag`], a
+    /// resource already in a manager is only retagged inside [`ResourceManager::update_resource`].
+    pub fn remove_tag(&mut self, tag: &ResourceTag) -> bool {
+        let before = self.tags.len();
+        self.tags.retain(|existing| existing != tag);
+        self.tags.len() != before
+    }
+
+    /// Replaces every tag, ignoring repeats. As with [`Resource::add_tag`], a resource already in a
+    /// manager is only retagged inside [`ResourceManager::update_resource`].
+    pub fn set_tags<I: IntoIterator<Item = ResourceTag>>(&mut self, tags: I) {
+        self.tags.clear();
+        for tag in tags {
+            self.add_tag(tag);
+        }
+    }
+
+    /// Whether this resource was created by a processed transformer
+    pub fn is_derived(&self) -> bool {
+        self.derived
+    }
+
+    pub fn extra(&self) -> &HashMap<String, Value> {
+        &self.extra
+    }
+
+    pub fn unlocked_by_default(&self) -> bool {
+        self.unlocked_by_default
+    }
+
+    /// Whether the resources are the same apart from their ids, such as the same resource added to
+    /// two managers
+    pub fn same_content(&self, other: &Resource) -> bool {
+        Resource {
+            id: other.id,
+            ..self.clone()
+        } == *other
+    }
+
+    /// Whether the resource still has the default colors, rather than explicitly chosen ones
+    pub fn has_default_colors(&self) -> bool {
+        self.fg_color == DEFAULT_FG_COLOR && self.bg_color == DEFAULT_BG_COLOR
+    }
+
+    /// Checks the resource for inconsistencies using every rule
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        self.validate_with(&ValidationRules::default())
+    }
+
+    /// Checks the resource for inconsistencies, returning every problem found
+    pub fn validate_with(&self, rules: &ValidationRules) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if rules.non_empty_name && self.name.trim().is_empty() {
+            problems.push("Resource has an empty name".to_string());
+        }
+        if rules.ingot_implies_metal
+            && self.contains_tag(&ResourceTag::Ingot)
+            && !self.contains_tag(&ResourceTag::Metal)
+        {
+            problems.push(format!("{} is an Ingot but not a Metal", self.name));
+        }
+        if rules.distinct_colors && self.fg_color == self.bg_color {
+            problems.push(format!(
+                "{} has the same foreground and background color",
+                self.name
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
     pub fn contains_tag(&self, tag: &ResourceTag) -> bool {
         self.tags.contains(tag)
     }
@@ -92,237 +450,2846 @@ impl Resource {
     }
 }
 
+/// Identifies a transformer added to a [`ResourceManager`]. Ids aren't reused after removal.
+pub type TransformerId = u64;
+
+/// Creates a resource from another, if it applies to it
+type Transform = Box<dyn Fn(&Resource) -> Option<Resource>>;
+
+/// Creates a resource from a pair of others, if it applies to them
+type PairTransform = Box<dyn Fn(&Resource, &Resource) -> Option<Resource>>;
+
+struct ProcessedTransformer {
+    id: TransformerId,
+    /// The name and name template given to a named transformer
+    name: Option<(String, String)>,
+    transform: Transform,
+    /// Whether the transformer also runs on resources created by transformers
+    apply_to_derived: bool,
+}
+
+impl ProcessedTransformer {
+    fn applies_to(&self, resource: &Resource) -> bool {
+        self.apply_to_derived || !resource.derived
+    }
+}
+
+struct PairTransformer {
+    id: TransformerId,
+    transform: PairTransform,
+}
+
+/// The order [`ResourceManager::resources_sorted`] lists resources in
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResourceOrder {
+    /// By id, which is the order resources were added in when ids are sequential
+    Id,
+    Name,
+    /// Lowest tier first, then by name
+    Tier,
+}
+
+/// How a [`ResourceManager`] gives ids to the resources added to it
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IdAssignment {
+    /// Counts up from 0 in the order resources are added, so ids are never reused but depend on
+    /// that order
+    Sequential,
+    /// Hashes the resource's name, so the same resources get the same ids whatever order they're
+    /// added in, and a removed resource gets its id back when added again. If another resource
+    /// already has the hashed id, the next free id up is used instead, and only then does the id
+    /// depend on which of the two was added first.
+    NameHash,
+}
+
+/// A hash of a resource name that stays the same across runs and builds, unlike the hashers in
+/// the standard library. This is 64 bit FNV-1a.
+pub(crate) fn stable_name_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// What [`ResourceManager::merge_with`] does with a resource whose name is already taken
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MergeCollision {
+    /// Fails the merge before anything is added
+    Error,
+    /// Keeps the resource already there, mapping the merged resource's id to it
+    KeepExisting,
+}
+
 pub struct ResourceManager {
-    resources: HashMap<u64, Resource>,
-    processed_transformations: Vec<Box<dyn Fn(&Resource) -> Option<Resource>>>,
+    /// Keyed by id, so iterating visits resources in the order they were added when ids are
+    /// sequential
+    resources: BTreeMap<u64, Resource>,
+    names: HashMap<String, u64>,
+    tag_index: HashMap<ResourceTag, HashSet<u64>>,
+    /// Kept in the order they were added, which is the order they run in
+    processed_transformations: Vec<ProcessedTransformer>,
+    /// Run after the single resource transformers, in the order they were added
+    pair_transformations: Vec<PairTransformer>,
+    transformers_created: TransformerId,
+    discovered: HashSet<u64>,
+    max_per_tag: HashMap<ResourceTag, usize>,
+    tag_implications: HashMap<ResourceTag, Vec<ResourceTag>>,
+    name_trie: NameTrie,
+    strict_validation: Option<ValidationRules>,
+    id_assignment: IdAssignment,
     resources_created: u64,
+    regex_cache: RegexCache,
+    /// The ids of the resources pair transformers look at, the first [`MAX_PAIRED_RESOURCES`] in id
+    /// order that weren't created by a transformer
+    pairable: BTreeSet<u64>,
+    /// Used instead of [`stable_name_hash`] when set, so tests can make names collide
+    #[cfg(test)]
+    name_hash_override: Option<fn(&str) -> u64>,
+}
+
+impl Default for ResourceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A resource in a manager being changed in place, from
+/// [`ResourceManager::resource_by_id_mut`]. The changes are made to a copy, which is put back
+/// through [`ResourceManager::update_resource`] when the guard is dropped, so the name and tag
+/// lookups can't go stale. Changes that it rejects are dropped, leaving the resource as it was;
+/// [`commit`](Self::commit) reports them instead.
+pub struct ResourceMut<'m> {
+    manager: &'m mut ResourceManager,
+    id: u64,
+    /// Only taken when the changes are put back
+    resource: Option<Resource>,
+}
+
+impl ResourceMut<'_> {
+    /// Puts the changes back now, returning why they were rejected if they were
+    pub fn commit(mut self) -> Result<(), ResourceError> {
+        let resource = self
+            .resource
+            .take()
+            .expect("changes are only put back once");
+        self.manager.update_resource(self.id, |old| *old = resource)
+    }
+}
+
+impl Deref for ResourceMut<'_> {
+    type Target = Resource;
+
+    fn deref(&self) -> &Resource {
+        self.resource
+            .as_ref()
+            .expect("changes are only put back once")
+    }
+}
+
+impl DerefMut for ResourceMut<'_> {
+    fn deref_mut(&mut self) -> &mut Resource {
+        self.resource
+            .as_mut()
+            .expect("changes are only put back once")
+    }
+}
+
+impl Drop for ResourceMut<'_> {
+    fn drop(&mut self) {
+        if let Some(resource) = self.resource.take() {
+            let _ = self.manager.update_resource(self.id, |old| *old = resource);
+        }
+    }
 }
 
 impl ResourceManager {
     pub fn new() -> Self {
         Self {
             resources: Default::default(),
+            names: Default::default(),
+            tag_index: Default::default(),
             processed_transformations: vec![],
+            pair_transformations: vec![],
+            transformers_created: 0,
+            discovered: Default::default(),
+            max_per_tag: Default::default(),
+            tag_implications: Default::default(),
+            name_trie: NameTrie::new(),
+            strict_validation: None,
+            id_assignment: IdAssignment::Sequential,
             resources_created: 0,
+            regex_cache: RegexCache::new(),
+            pairable: Default::default(),
+            #[cfg(test)]
+            name_hash_override: None,
         }
     }
 
-    pub fn add_processed_transformer<F>(&mut self, transformer: F) -> Result<(), String>
-    where
-        F: 'static + Fn(&Resource) -> Option<Resource>,
-    {
-        let mut to_add = Vec::new();
-        for resource in self.resources.values() {
-            if let Some(processed) = transformer(resource) {
-                to_add.push(processed);
-            }
+    /// When set, resources that fail validation with the given rules can't be added
+    pub fn set_strict_validation(&mut self, rules: Option<ValidationRules>) {
+        self.strict_validation = rules;
+    }
+
+    /// Sets how ids are given to resources added from now on. Ids are sequential unless changed, so
+    /// set this before adding any resources for ids that don't depend on the order they're added.
+    pub fn set_id_assignment(&mut self, id_assignment: IdAssignment) {
+        self.id_assignment = id_assignment;
+    }
+
+    pub fn id_assignment(&self) -> IdAssignment {
+        self.id_assignment
+    }
+
+    /// Limits how many resources may have a tag, guarding against runaway transformers. Adding a
+    /// resource that would exceed the limit fails, including resources created by transformers.
+    pub fn set_tag_limit(&mut self, tag: ResourceTag, limit: usize) {
+        self.max_per_tag.insert(tag, limit);
+    }
+
+    pub fn remove_tag_limit(&mut self, tag: &ResourceTag) {
+        self.max_per_tag.remove(tag);
+    }
+
+    /// Makes every resource tagged `tag` also tagged `implied`, including resources already added.
+    /// Implications are transitive, so one that would lead a tag back to itself is an error.
+    pub fn add_tag_implication(
+        &mut self,
+        tag: ResourceTag,
+        implied: ResourceTag,
+    ) -> Result<(), String> {
+        if self.implied_tags(&implied).contains(&tag) {
+            return Err(format!(
+                "Tag implication {} -> {} would be cyclic",
+                tag, implied
+            ));
         }
-        for resource in to_add {
-            self.add_resource(resource)?;
+        self.tag_implications.entry(tag).or_default().push(implied);
+        let ids = self.resources.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            let tags = self.with_implied_tags(&self.resources[&id].tags);
+            for tag in &tags {
+                self.tag_index.entry(tag.clone()).or_default().insert(id);
+            }
+            self.resources.get_mut(&id).unwrap().tags = tags;
         }
-        self.processed_transformations.push(Box::new(transformer));
         Ok(())
     }
 
-    pub fn add_resource(&mut self, mut resource: Resource) -> Result<u64, String> {
-        if self.resource_by_name(resource.name()).is_some() {
-            return Err("Resource already exists".to_string());
+    /// Gets a tag followed by every tag it implies, directly or not
+    pub fn implied_tags(&self, tag: &ResourceTag) -> Vec<ResourceTag> {
+        let mut implied = vec![tag.clone()];
+        let mut next = 0;
+        while next < implied.len() {
+            if let Some(direct) = self.tag_implications.get(&implied[next]) {
+                for tag in direct {
+                    if !implied.contains(tag) {
+                        implied.push(tag.clone());
+                    }
+                }
+            }
+            next += 1;
         }
-        let resource_id = self.resources_created;
-        self.resources_created += 1;
-        resource.id = resource_id;
-        self.resources.insert(resource_id, resource);
-        let resource = &self.resources[&resource_id];
+        implied
+    }
+
+    fn with_implied_tags(&self, tags: &[ResourceTag]) -> Vec<ResourceTag> {
+        let mut all = Vec::new();
+        for tag in tags {
+            for implied in self.implied_tags(tag) {
+                if !all.contains(&implied) {
+                    all.push(implied);
+                }
+            }
+        }
+        all
+    }
+
+    /// Adds a transformer that runs on every resource, including ones created by transformers
+    pub fn add_processed_transformer<F>(&mut self, transformer: F) -> Result<TransformerId, String>
+    where
+        F: 'static + Fn(&Resource) -> Option<Resource>,
+    {
+        self.add_processed_transformer_with(transformer, true)
+    }
+
+    /// Adds a transformer, which only runs on resources created by other transformers if
+    /// `apply_to_derived` is set. It runs on every resource already added, then on each resource
+    /// added later, after the transformers added before it.
+    pub fn add_processed_transformer_with<F>(
+        &mut self,
+        transformer: F,
+        apply_to_derived: bool,
+    ) -> Result<TransformerId, String>
+    where
+        F: 'static + Fn(&Resource) -> Option<Resource>,
+    {
+        self.register_transformer(transformer, None, apply_to_derived)
+    }
+
+    fn register_transformer<F>(
+        &mut self,
+        transformer: F,
+        name: Option<(String, String)>,
+        apply_to_derived: bool,
+    ) -> Result<TransformerId, String>
+    where
+        F: 'static + Fn(&Resource) -> Option<Resource>,
+    {
+        let id = self.transformers_created;
+        let transformer = ProcessedTransformer {
+            id,
+            name,
+            transform: Box::new(transformer),
+            apply_to_derived,
+        };
         let mut to_add = Vec::new();
-        for transformer in &self.processed_transformations {
-            if let Some(mut processed) = transformer(resource) {
+        for resource in self.resources.values() {
+            if !transformer.applies_to(resource) {
+                continue;
+            }
+            if let Some(processed) = (transformer.transform)(resource) {
                 to_add.push(processed);
             }
         }
+        // Registered first so it also runs on what it creates, which is where cycles show up
+        self.processed_transformations.push(transformer);
+        let (mut added, resources_created) = (vec![], self.resources_created);
         for resource in to_add {
-            self.add_resource(resource)?;
+            if let Err(e) = self.insert_resource(resource, 1, &mut added) {
+                self.processed_transformations.pop();
+                self.roll_back(added, resources_created);
+                return Err(e.to_string());
+            }
         }
-        Ok(resource_id)
+        self.transformers_created += 1;
+        Ok(id)
     }
 
-    pub fn resources(&self) -> impl Iterator<Item = &Resource> {
-        self.resources.values()
+    /// Adds a transformer along with a name and the template of the names it produces, such as
+    /// `"{name} Ingot"`, so the naming rules can be listed by [`derived_name_patterns`](Self::derived_name_patterns)
+    pub fn add_named_processed_transformer<S1, S2, F>(
+        &mut self,
+        name: S1,
+        template: S2,
+        transformer: F,
+    ) -> Result<TransformerId, String>
+    where
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+        F: 'static + Fn(&Resource) -> Option<Resource>,
+    {
+        let name = (name.as_ref().to_string(), template.as_ref().to_string());
+        self.register_transformer(transformer, Some(name), true)
     }
 
-    pub fn resource_by_name<S: AsRef<str>>(&self, name: S) -> Option<&Resource> {
-        let name = name.as_ref();
-        self.resources.values().find(|res| res.name() == name)
+    /// Adds a transformer that combines two different resources, such as two metals into an alloy.
+    /// It is called with every ordered pair of resources that weren't created by a transformer,
+    /// so it sees both `(a, b)` and `(b, a)`, then with each such resource added later paired with
+    /// every other one. Only the first [`MAX_PAIRED_RESOURCES`] of those resources are paired.
+    /// Resources it creates whose name already exists are skipped, so both orders of a pair may
+    /// give the same resource. What it creates goes through the single resource transformers, but
+    /// isn't paired itself.
+    pub fn add_pair_transformer<F>(&mut self, transformer: F) -> Result<TransformerId, String>
+    where
+        F: 'static + Fn(&Resource, &Resource) -> Option<Resource>,
+    {
+        let id = self.transformers_created;
+        self.pair_transformations.push(PairTransformer {
+            id,
+            transform: Box::new(transformer),
+        });
+        let to_add = self.pair_outputs(self.pair_transformations.len() - 1, None);
+        let (mut added, resources_created) = (vec![], self.resources_created);
+        for resource in to_add {
+            if self.contains_name(resource.name()) {
+                continue;
+            }
+            if let Err(e) = self.insert_resource(resource, 1, &mut added) {
+                self.pair_transformations.pop();
+                self.roll_back(added, resources_created);
+                return Err(e.to_string());
+            }
+        }
+        self.transformers_created += 1;
+        Ok(id)
     }
 
-    pub fn resource_by_id(&self, id: u64) -> Option<&Resource> {
-        self.resources.values().find(|res| res.id == id)
+    /// Finds the pairable resources again, such as after one of them is removed
+    fn refresh_pairable(&mut self) {
+        self.pairable = self
+            .resources
+            .values()
+            .filter(|resource| !resource.derived)
+            .map(|resource| resource.id)
+            .take(MAX_PAIRED_RESOURCES)
+            .collect();
     }
 
-    pub fn resource_id_by_name<S: AsRef<str>>(&self, name: S) -> Option<u64> {
-        let name = name.as_ref();
-        self.resources
-            .values()
-            .find(|res| res.name() == name)
-            .map(|res| res.id)
+    /// Adds a resource that wasn't created by a transformer to the pairable resources, if it's
+    /// among the first [`MAX_PAIRED_RESOURCES`] of them
+    fn add_pairable(&mut self, id: u64) {
+        self.pairable.insert(id);
+        if self.pairable.len() > MAX_PAIRED_RESOURCES {
+            let last = *self.pairable.iter().next_back().unwrap();
+            self.pairable.remove(&last);
+        }
     }
 
-    pub fn resources_by_regular_expression(&self, regex: &Regex) -> Vec<(&Resource, Captures)> {
-            self.resources()
-                .filter_map(|resource| {
-                    let name = resource.name();
-                    if let Some(matched) = regex.captures(name.as_str()) {
-                        Some((resource, matched))
+    /// Runs a pair transformer on every ordered pair of pairable resources, or if given only on the
+    /// pairs including `newcomer`, which is then only paired if it is pairable itself
+    fn pair_outputs(&self, transformer: usize, newcomer: Option<u64>) -> Vec<Resource> {
+        let transform = &self.pair_transformations[transformer].transform;
+        let pairable = |id: &u64| &self.resources[id];
+        let mut outputs = Vec::new();
+        match newcomer {
+            Some(newcomer) if self.pairable.contains(&newcomer) => {
+                let newcomer = pairable(&newcomer);
+                // In the same order as when every pair is run
+                for first in self.pairable.iter().map(pairable) {
+                    if first.id == newcomer.id {
+                        for second in self.pairable.iter().map(pairable) {
+                            if second.id != newcomer.id {
+                                outputs.extend(transform(newcomer, second));
+                            }
+                        }
                     } else {
-                        None
+                        outputs.extend(transform(first, newcomer));
                     }
-                })
-                .collect()
+                }
+            }
+            Some(_) => {}
+            None => {
+                for first in self.pairable.iter().map(pairable) {
+                    for second in self.pairable.iter().map(pairable) {
+                        if first.id != second.id {
+                            outputs.extend(transform(first, second));
+                        }
+                    }
+                }
+            }
+        }
+        outputs
     }
 
-    pub fn resources_with_tag(&self, tag: &ResourceTag) -> Vec<&Resource> {
-        self.resources()
-            .filter(|res| res.contains_tag(tag))
+    /// Gets the (transformer name, name template) pairs of every named transformer, in the order
+    /// they were added
+    pub fn derived_name_patterns(&self) -> Vec<(String, String)> {
+        self.processed_transformations
+            .iter()
+            .filter_map(|transformer| transformer.name.clone())
             .collect()
     }
 
-    pub fn resources_with_tags<'a, I : IntoIterator<Item=&'a ResourceTag> + Clone>(&self, tags: I) -> Vec<&Resource> {
-        self.resources()
-            .filter(|res| res.contains_all_tags(tags.clone()))
+    /// Gets the id and name, if it has one, of every transformer, in the order they run. Pair
+    /// transformers come last and have no name.
+    pub fn transformers(&self) -> Vec<(TransformerId, Option<&str>)> {
+        self.processed_transformations
+            .iter()
+            .map(|transformer| {
+                let name = transformer.name.as_ref().map(|(name, _)| name.as_str());
+                (transformer.id, name)
+            })
+            .chain(
+                self.pair_transformations
+                    .iter()
+                    .map(|transformer| (transformer.id, None)),
+            )
             .collect()
     }
-}
 
-impl Debug for ResourceManager {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ResourceManager {{")?;
-        write!(
-            f,
-            "{}",
-            self.resources()
-                .map(|res| res.name().clone())
-                .collect::<Vec<String>>()
-                .join(", ")
-        )?;
-        write!(f, "}}")
+    /// Removes a transformer, returning whether it existed. Resources it already created are kept.
+    pub fn remove_transformer(&mut self, id: TransformerId) -> bool {
+        let before = self.processed_transformations.len() + self.pair_transformations.len();
+        self.processed_transformations
+            .retain(|transformer| transformer.id != id);
+        self.pair_transformations
+            .retain(|transformer| transformer.id != id);
+        self.processed_transformations.len() + self.pair_transformations.len() != before
     }
-}
-
-pub fn setup_resource_manager(manager: &mut ResourceManager) -> Result<(), String> {
-    manager.add_resource(Resource::new(
-        "Iron",
-        "Fe",
-        "",
-        Color::WHITE,
-        Color::BLACK,
-        &[ResourceTag::Metal, ResourceTag::Base],
-    ))?;
-    manager.add_resource(Resource::new(
-        "Copper",
-        "Fe",
-        "",
-        Color::WHITE,
-        Color::BLACK,
-        &[ResourceTag::Metal, ResourceTag::Base],
-    ))?;
-    manager.add_resource(Resource::new(
-        "Gold",
-        "Fe",
-        "",
-        Color::WHITE,
-        Color::BLACK,
-        &[ResourceTag::Metal, ResourceTag::Base],
-    ))?;
 
-    // transformers first
-    manager.add_processed_transformer(|resource| {
-        if resource.contains_all_tags(&[ResourceTag::Base, ResourceTag::Metal]) {
-            Some(Resource::new(
-                format!("{} Ingot", resource.name()),
-                format!("A pure form of {} in a convenient bar form", resource.name),
-                "",
-                resource.fg_color,
-                resource.bg_color,
-                &[ResourceTag::Metal, ResourceTag::Ingot],
-            ))
-        } else {
-            None
+    /// Runs every transformer on every resource again, adding whatever they create that doesn't
+    /// exist yet, such as resources removed since a transformer created them. Resources are
+    /// visited in id order, each going through the transformers in the order they were added, and
+    /// this repeats over the new resources until nothing new is created. Pair transformers run
+    /// once over every pair first. Returns the ids of the resources created.
+    pub fn reapply_all_transformers(&mut self) -> Result<Vec<u64>, String> {
+        let mut added = Vec::new();
+        let mut pending = self.resources.keys().copied().collect::<Vec<_>>();
+        let paired = (0..self.pair_transformations.len())
+            .flat_map(|transformer| self.pair_outputs(transformer, None))
+            .collect::<Vec<_>>();
+        for resource in paired {
+            if !self.contains_name(resource.name()) {
+                let id = self
+                    .store_resource(resource, true)
+                    .map_err(|e| e.to_string())?;
+                pending.push(id);
+                added.push(id);
+            }
         }
-    })?;
-    manager.add_processed_transformer(|resource| {
-        if resource.contains_all_tags(&[ResourceTag::Base, ResourceTag::Metal]) {
+        for _ in 0..=MAX_DERIVATION_DEPTH {
+            if pending.is_empty() {
+                return Ok(added);
+            }
+            let mut to_add = Vec::new();
+            for id in &pending {
+                let resource = &self.resources[id];
+                for transformer in &self.processed_transformations {
+                    if !transformer.applies_to(resource) {
+                        continue;
+                    }
+                    if let Some(processed) = (transformer.transform)(resource) {
+                        to_add.push(processed);
+                    }
+                }
+            }
+            pending.clear();
+            for resource in to_add {
+                if !self.contains_name(resource.name()) {
+                    pending.push(
+                        self.store_resource(resource, true)
+                            .map_err(|e| e.to_string())?,
+                    );
+                }
+            }
+            added.extend(&pending);
+        }
+        Err("transformer produced a cyclic resource".to_string())
+    }
+
+    pub fn add_resource(&mut self, resource: Resource) -> Result<u64, ResourceError> {
+        let (mut added, resources_created) = (vec![], self.resources_created);
+        let result = self.insert_resource(resource, 0, &mut added);
+        if result.is_err() {
+            self.roll_back(added, resources_created);
+        }
+        result
+    }
+
+    /// Adds each resource in turn, running the transformers on each one added. Resources that can't
+    /// be added don't stop the rest, and are given back along with why, in the same position.
+    pub fn add_resources<I: IntoIterator<Item = Resource>>(
+        &mut self,
+        resources: I,
+    ) -> Vec<Result<u64, (Resource, ResourceError)>> {
+        let mut results = Vec::new();
+        for resource in resources {
+            results.push(match self.add_resource(resource.clone()) {
+                Ok(id) => Ok(id),
+                Err(reason) => Err((resource, reason)),
+            });
+        }
+        results
+    }
+
+    /// Adds every resource defined in a JSON file holding a list of [`ResourceDef`]s, in order,
+    /// returning their ids. Stops at the first resource that can't be added.
+    pub fn load_resources<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u64>, String> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).map_err(|e| format!("Couldn't open {}: {}", path.display(), e))?;
+        let defs: Vec<ResourceDef> = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("Couldn't read resources from {}: {}", path.display(), e))?;
+        self.add_resource_defs(defs)
+    }
+
+    /// Adds every resource defined, in order, returning their ids. Stops at the first resource
+    /// that can't be added.
+    pub fn add_resource_defs(&mut self, defs: Vec<ResourceDef>) -> Result<Vec<u64>, String> {
+        defs.into_iter()
+            .map(|def| {
+                let name = def.name.clone();
+                self.add_resource(def.into())
+                    .map_err(|e| format!("Couldn't add {}: {}", name, e))
+            })
+            .collect()
+    }
+
+    /// Adds every resource of `other`, such as a mod's, giving each a new id, and returns the new id
+    /// of each of `other`'s ids so recipes written against it can be rewritten. Fails if any
+    /// resource has the name of one already here; see [`merge_with`](Self::merge_with).
+    pub fn merge(&mut self, other: ResourceManager) -> Result<HashMap<u64, u64>, String> {
+        self.merge_with(other, MergeCollision::Error)
+            .map(|(ids, _)| ids)
+    }
+
+    /// Adds every resource of `other` in the order they were added there, giving each a new id.
+    /// Returns the new id of each of `other`'s ids, and the ids in `other` of resources whose name
+    /// was taken, which are handled by `on_collision`.
+    ///
+    /// Resources keep whether they were derived and whether they were discovered, but `other`'s
+    /// transformers are dropped, and this manager's transformers aren't run on the merged
+    /// resources. Use [`reapply_all_transformers`](Self::reapply_all_transformers) to derive from
+    /// them. Implied tags, tag limits and strict validation apply as when adding a resource, and a
+    /// resource failing those stops the merge part way, with the resources before it merged.
+    pub fn merge_with(
+        &mut self,
+        other: ResourceManager,
+        on_collision: MergeCollision,
+    ) -> Result<(HashMap<u64, u64>, Vec<u64>), String> {
+        let collisions = other
+            .resources()
+            .filter(|resource| self.contains_name(resource.name()))
+            .map(Resource::id)
+            .collect::<Vec<_>>();
+        if on_collision == MergeCollision::Error && !collisions.is_empty() {
+            let names = collisions
+                .iter()
+                .map(|id| other.resources[id].name().as_str())
+                .collect::<Vec<_>>();
+            return Err(format!("Resources already exist: {}", names.join(", ")));
+        }
+
+        let mut ids = HashMap::new();
+        for (old_id, resource) in other.resources {
+            let new_id = match self.resource_id_by_name(resource.name()) {
+                Some(existing) => existing,
+                None => {
+                    let derived = resource.derived;
+                    self.store_resource(resource, derived)
+                        .map_err(|e| e.to_string())?
+                }
+            };
+            if other.discovered.contains(&old_id) {
+                self.discovered.insert(new_id);
+            }
+            ids.insert(old_id, new_id);
+        }
+        Ok((ids, collisions))
+    }
+
+    /// Adds a resource created by a chain of `depth` transformers, then runs the transformers on
+    /// it. The ids of the resources added are pushed to `added`, so they can be rolled back if a
+    /// later one fails.
+    fn insert_resource(
+        &mut self,
+        resource: Resource,
+        depth: usize,
+        added: &mut Vec<u64>,
+    ) -> Result<u64, ResourceError> {
+        if depth > MAX_DERIVATION_DEPTH {
+            return Err(ResourceError::CyclicDerivation);
+        }
+        let resource_id = self.store_resource(resource, depth > 0)?;
+        added.push(resource_id);
+        let resource = &self.resources[&resource_id];
+        let mut to_add = Vec::new();
+        for transformer in &self.processed_transformations {
+            if !transformer.applies_to(resource) {
+                continue;
+            }
+            if let Some(processed) = (transformer.transform)(resource) {
+                to_add.push(processed);
+            }
+        }
+        for resource in to_add {
+            self.insert_resource(resource, depth + 1, added)?;
+        }
+        if depth == 0 {
+            let to_add = (0..self.pair_transformations.len())
+                .flat_map(|transformer| self.pair_outputs(transformer, Some(resource_id)))
+                .collect::<Vec<_>>();
+            for resource in to_add {
+                if !self.contains_name(resource.name()) {
+                    self.insert_resource(resource, 1, added)?;
+                }
+            }
+        }
+        Ok(resource_id)
+    }
+
+    /// Checks and adds a single resource without running the transformers on it
+    fn store_resource(
+        &mut self,
+        mut resource: Resource,
+        derived: bool,
+    ) -> Result<u64, ResourceError> {
+        if self.resource_by_name(resource.name()).is_some() {
+            return Err(ResourceError::AlreadyExists(resource.name().clone()));
+        }
+        resource.tags = self.with_implied_tags(&resource.tags);
+        if let Some(rules) = &self.strict_validation {
+            resource
+                .validate_with(rules)
+                .map_err(ResourceError::Invalid)?;
+        }
+        self.check_tag_limits(resource.tags())?;
+        let resource_id = self.next_id(resource.name());
+        resource.id = resource_id;
+        resource.derived = derived;
+        self.index(&resource);
+        self.resources.insert(resource_id, resource);
+        if !derived {
+            self.add_pairable(resource_id);
+        }
+        Ok(resource_id)
+    }
+
+    /// Puts back a resource as it was saved, keeping its id and whether a transformer created it,
+    /// without running the transformers or checks on it. A resource with the same id and name is
+    /// replaced by it.
+    pub(crate) fn restore_resource(&mut self, resource: Resource) -> Result<(), ResourceError> {
+        let id = resource.id;
+        match self.resources.get(&id) {
+            Some(existing) if existing.name() != resource.name() => {
+                return Err(ResourceError::IdTaken {
+                    id,
+                    name: existing.name().clone(),
+                });
+            }
+            None if self.contains_name(resource.name()) => {
+                return Err(ResourceError::AlreadyExists(resource.name().clone()));
+            }
+            _ => {}
+        }
+        if let Some(old) = self.resources.remove(&id) {
+            self.unindex(&old);
+        }
+        let was_pairable = self.pairable.remove(&id);
+        let derived = resource.derived;
+        self.index(&resource);
+        self.resources.insert(id, resource);
+        if !derived {
+            self.add_pairable(id);
+        } else if was_pairable {
+            self.refresh_pairable();
+        }
+        if self.id_assignment == IdAssignment::Sequential {
+            self.resources_created = self.resources_created.max(id.saturating_add(1));
+        }
+        Ok(())
+    }
+
+    /// Picks the id of a new resource, as set by [`set_id_assignment`](Self::set_id_assignment)
+    fn next_id(&mut self, name: &str) -> u64 {
+        match self.id_assignment {
+            IdAssignment::Sequential => {
+                let id = self.resources_created;
+                self.resources_created += 1;
+                id
+            }
+            IdAssignment::NameHash => {
+                let mut id = self.name_hash(name);
+                while self.resources.contains_key(&id) {
+                    id = id.wrapping_add(1);
+                }
+                id
+            }
+        }
+    }
+
+    /// The id first tried for a resource under [`IdAssignment::NameHash`]
+    fn name_hash(&self, name: &str) -> u64 {
+        #[cfg(test)]
+        if let Some(hash) = self.name_hash_override {
+            return hash(name);
+        }
+        stable_name_hash(name)
+    }
+
+    /// Removes a resource, returning it if it existed. Sequential ids are never reused, so a recipe
+    /// still referring to a removed resource can't silently refer to a newer one. Name hash ids
+    /// are reused, by the same resource added again or by one whose name hashes to the same id,
+    /// so with those a recipe referring to a removed resource may refer to a newer one. Use
+    /// [`RecipeLoader::recipes_referencing`](crate::processing::recipe_loader::RecipeLoader::recipes_referencing)
+    /// to find recipes that would be left dangling.
+    pub fn remove_resource(&mut self, id: u64) -> Option<Resource> {
+        let resource = self.resources.remove(&id)?;
+        self.unindex(&resource);
+        self.discovered.remove(&id);
+        if self.pairable.remove(&id) {
+            self.refresh_pairable();
+        }
+        Some(resource)
+    }
+
+    /// Removes the resources added by a call that failed part way, and frees their sequential ids,
+    /// which were never handed out
+    fn roll_back(&mut self, added: Vec<u64>, resources_created: u64) {
+        for id in added {
+            self.remove_resource(id);
+        }
+        self.resources_created = resources_created;
+    }
+
+    /// Errors if adding a resource with the tags would exceed the limit of any of them
+    fn check_tag_limits<'t, I: IntoIterator<Item = &'t ResourceTag>>(
+        &self,
+        tags: I,
+    ) -> Result<(), ResourceError> {
+        for tag in tags {
+            if let Some(&limit) = self.max_per_tag.get(tag) {
+                if self.resources_with_tag(tag).len() >= limit {
+                    return Err(ResourceError::TagLimitExceeded {
+                        tag: tag.clone(),
+                        limit,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a resource to the name, tag and prefix indexes
+    fn index(&mut self, resource: &Resource) {
+        self.names.insert(resource.name().clone(), resource.id);
+        for tag in &resource.tags {
+            self.tag_index
+                .entry(tag.clone())
+                .or_default()
+                .insert(resource.id);
+        }
+        self.name_trie.insert(resource.name(), resource.id);
+    }
+
+    /// Removes a resource from the name, tag and prefix indexes
+    fn unindex(&mut self, resource: &Resource) {
+        self.names.remove(resource.name());
+        for tag in &resource.tags {
+            if let Some(ids) = self.tag_index.get_mut(tag) {
+                ids.remove(&resource.id);
+            }
+        }
+        self.name_trie.remove(resource.name(), resource.id);
+    }
+
+    /// Gets a resource to change in place, behind a guard that puts the changes back through
+    /// [`update_resource`](Self::update_resource) when it's dropped
+    pub fn resource_by_id_mut(&mut self, id: u64) -> Option<ResourceMut<'_>> {
+        let resource = self.resource_by_id(id)?.clone();
+        Some(ResourceMut {
+            manager: self,
+            id,
+            resource: Some(resource),
+        })
+    }
+
+    /// Changes a resource in place, keeping its id, and updates the name and tag lookups to match.
+    /// Every change to a resource once it's added goes through here, so the lookups can't go
+    /// stale. Implied tags, tag limits and strict validation apply as when adding a resource.
+    /// Nothing changes if the resource doesn't exist, the update fails those checks, or it renames
+    /// the resource to the name of another one.
+    pub fn update_resource<F: FnOnce(&mut Resource)>(
+        &mut self,
+        id: u64,
+        update: F,
+    ) -> Result<(), ResourceError> {
+        let old = self.resource_by_id_res(id)?;
+        let mut updated = old.clone();
+        update(&mut updated);
+        updated.id = old.id;
+        updated.derived = old.derived;
+        if updated.name() != old.name() && self.names.contains_key(updated.name()) {
+            return Err(ResourceError::AlreadyExists(updated.name().clone()));
+        }
+        updated.tags = self.with_implied_tags(&updated.tags);
+        if let Some(rules) = &self.strict_validation {
+            updated
+                .validate_with(rules)
+                .map_err(ResourceError::Invalid)?;
+        }
+        self.check_tag_limits(updated.tags().iter().filter(|tag| !old.contains_tag(tag)))?;
+
+        let old = self.resources.remove(&id).unwrap();
+        self.unindex(&old);
+        self.index(&updated);
+        self.resources.insert(id, updated);
+        Ok(())
+    }
+
+    pub fn remove_resource_by_name<S: AsRef<str>>(&mut self, name: S) -> Option<Resource> {
+        let id = self.resource_id_by_name(name)?;
+        self.remove_resource(id)
+    }
+
+    pub fn resources(&self) -> impl Iterator<Item = &Resource> {
+        self.resources.values()
+    }
+
+    /// How many resources there are, including derived ones
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    pub fn contains_name(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    pub fn contains_id(&self, id: u64) -> bool {
+        self.resources.contains_key(&id)
+    }
+
+    /// Checks the icon of every resource, relative to `base_dir`, returning the ids of resources
+    /// whose icons can't be used along with why, ordered by id. Resources without an icon are
+    /// skipped.
+    pub fn validate_icons<P: AsRef<Path>>(&self, base_dir: P) -> Vec<(u64, IconError)> {
+        self.resources()
+            .filter(|res| !res.base_icon().as_os_str().is_empty())
+            .filter_map(|res| {
+                check_icon(&base_dir.as_ref().join(res.base_icon()))
+                    .err()
+                    .map(|e| (res.id(), e))
+            })
+            .collect()
+    }
+
+    pub fn resources_sorted(&self, order: ResourceOrder) -> Vec<&Resource> {
+        let mut resources = self.resources().collect::<Vec<_>>();
+        match order {
+            ResourceOrder::Id => {}
+            ResourceOrder::Name => {
+                resources.sort_by(|a, b| a.name().cmp(b.name()).then(a.id.cmp(&b.id)))
+            }
+            ResourceOrder::Tier => resources.sort_by(|a, b| {
+                a.tier
+                    .cmp(&b.tier)
+                    .then_with(|| a.name().cmp(b.name()))
+                    .then(a.id.cmp(&b.id))
+            }),
+        }
+        resources
+    }
+
+    pub fn resource_by_name<S: AsRef<str>>(&self, name: S) -> Option<&Resource> {
+        self.resource_id_by_name(name)
+            .and_then(|id| self.resources.get(&id))
+    }
+
+    pub fn resource_by_id(&self, id: u64) -> Option<&Resource> {
+        self.resources.get(&id)
+    }
+
+    pub fn resource_by_name_res<S: AsRef<str>>(&self, name: S) -> Result<&Resource, ResourceError> {
+        let name = name.as_ref();
+        self.resource_by_name(name)
+            .ok_or_else(|| ResourceError::NotFound(name.to_string()))
+    }
+
+    pub fn resource_by_id_res(&self, id: u64) -> Result<&Resource, ResourceError> {
+        self.resource_by_id(id)
+            .ok_or_else(|| ResourceError::NotFound(format!("#{}", id)))
+    }
+
+    pub fn resource_id_by_name<S: AsRef<str>>(&self, name: S) -> Option<u64> {
+        self.names.get(name.as_ref()).copied()
+    }
+
+    /// Gets every resource whose name starts with the prefix, ordered by name. Backed by a prefix
+    /// tree, so it takes time proportional to the prefix and the number of results.
+    pub fn prefix_search(&self, prefix: &str) -> Vec<&Resource> {
+        self.name_trie
+            .with_prefix(prefix)
+            .into_iter()
+            .map(|id| &self.resources[&id])
+            .collect()
+    }
+
+    /// Finds every resource whose name contains the query, ignoring case. Exact matches come first,
+    /// then names starting with the query, then the rest, each ordered by name. An empty query
+    /// finds nothing.
+    pub fn search(&self, query: &str) -> Vec<&Resource> {
+        if query.is_empty() {
+            return vec![];
+        }
+        let query = query.to_lowercase();
+        let mut found = self
+            .resources()
+            .filter_map(|resource| {
+                let name = resource.name().to_lowercase();
+                let rank = if name == query {
+                    0
+                } else if name.starts_with(&query) {
+                    1
+                } else if name.contains(&query) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, resource))
+            })
+            .collect::<Vec<_>>();
+        found.sort_by(|(rank_a, a), (rank_b, b)| {
+            rank_a
+                .cmp(rank_b)
+                .then(a.name().cmp(b.name()))
+                .then(a.id.cmp(&b.id))
+        });
+        found.into_iter().map(|(_, resource)| resource).collect()
+    }
+
+    pub fn resources_by_regular_expression(
+        &self,
+        regex: &Regex,
+    ) -> Vec<(&Resource, Captures<'_>)> {
+        self.resources()
+            .filter_map(|resource| {
+                let name = resource.name();
+                regex
+                    .captures(name.as_str())
+                    .map(|matched| (resource, matched))
+            })
+            .collect()
+    }
+
+    /// Finds the resources whose name matches a pattern, like
+    /// [`resources_by_regular_expression`](Self::resources_by_regular_expression), compiling the
+    /// pattern through [`regex_cache`](Self::regex_cache) so a pattern used again isn't compiled
+    /// again
+    pub fn resources_by_regular_expression_str(
+        &mut self,
+        pattern: &str,
+    ) -> Result<Vec<(&Resource, Captures<'_>)>, regex::Error> {
+        let regex = self.regex_cache.get(pattern)?;
+        Ok(self.resources_by_regular_expression(&regex))
+    }
+
+    /// The patterns compiled by
+    /// [`resources_by_regular_expression_str`](Self::resources_by_regular_expression_str)
+    pub fn regex_cache(&self) -> &RegexCache {
+        &self.regex_cache
+    }
+
+    /// Gives access to the pattern cache, such as to change its capacity
+    pub fn regex_cache_mut(&mut self) -> &mut RegexCache {
+        &mut self.regex_cache
+    }
+
+    /// Gets the text of the named capture group for every resource whose name matches, ordered by
+    /// id. Matches where the group didn't take part are skipped.
+    pub fn resources_by_named_capture(
+        &self,
+        regex: &Regex,
+        group: &str,
+    ) -> Vec<(&Resource, String)> {
+        self.resources_by_regular_expression(regex)
+            .into_iter()
+            .filter_map(|(resource, captures)| {
+                captures
+                    .name(group)
+                    .map(|matched| (resource, matched.as_str().to_string()))
+            })
+            .collect()
+    }
+
+    /// Gets the resources of a tier, ordered by id
+    pub fn resources_by_tier(&self, tier: u8) -> Vec<&Resource> {
+        self.resources_where(move |res| res.tier == tier).collect()
+    }
+
+    /// Gets the resources with the tag, ordered by id
+    pub fn resources_with_tag(&self, tag: &ResourceTag) -> Vec<&Resource> {
+        self.resources_by_id(self.tag_index.get(tag).into_iter().flatten().copied())
+    }
+
+    /// Gets the resources with all of the tags, ordered by id
+    pub fn resources_with_tags<'a, I : IntoIterator<Item=&'a ResourceTag> + Clone>(&self, tags: I) -> Vec<&Resource> {
+        let mut sets = Vec::new();
+        for tag in tags {
+            match self.tag_index.get(tag) {
+                Some(ids) => sets.push(ids),
+                None => return vec![],
+            }
+        }
+        sets.sort_by_key(|ids| ids.len());
+        match sets.split_first() {
+            Some((smallest, rest)) => self.resources_by_id(
+                smallest
+                    .iter()
+                    .copied()
+                    .filter(|id| rest.iter().all(|ids| ids.contains(id))),
+            ),
+            None => self.resources().collect(),
+        }
+    }
+
+    /// Gets the resources with at least one of the tags, ordered by id
+    pub fn resources_with_any_tag<'a, I>(&self, tags: I) -> Vec<&Resource>
+    where
+        I: IntoIterator<Item = &'a ResourceTag>,
+    {
+        let ids = tags
+            .into_iter()
+            .filter_map(|tag| self.tag_index.get(tag))
+            .flatten()
+            .copied()
+            .collect::<HashSet<_>>();
+        self.resources_by_id(ids)
+    }
+
+    /// Gets the resources without the tag, ordered by id
+    pub fn resources_without_tag(&self, tag: &ResourceTag) -> Vec<&Resource> {
+        self.resources()
+            .filter(|res| !res.contains_tag(tag))
+            .collect()
+    }
+
+    /// Gets the resources with every tag in `include` and none of the tags in `exclude`, ordered
+    /// by id. Implied tags count for both.
+    pub fn resources_matching(
+        &self,
+        include: &[ResourceTag],
+        exclude: &[ResourceTag],
+    ) -> Vec<&Resource> {
+        self.resources_with_tags(include)
+            .into_iter()
+            .filter(|res| !exclude.iter().any(|tag| res.contains_tag(tag)))
+            .collect()
+    }
+
+    fn resources_by_id<I: IntoIterator<Item = u64>>(&self, ids: I) -> Vec<&Resource> {
+        let mut ids = ids.into_iter().collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.iter().map(|id| &self.resources[id]).collect()
+    }
+
+    /// Colors every resource that still has the default colors using the scheme. Resources
+    /// with explicitly chosen colors are left alone.
+    pub fn autocolor(&mut self, scheme: ColorScheme) {
+        for resource in self.resources.values_mut() {
+            if resource.has_default_colors() {
+                let (fg_color, bg_color) = scheme.colors_for(resource);
+                resource.fg_color = fg_color;
+                resource.bg_color = bg_color;
+            }
+        }
+    }
+
+    /// Lazily visits the resources matching a predicate, ordered by id
+    pub fn resources_where<'a, F: Fn(&Resource) -> bool + 'a>(
+        &'a self,
+        pred: F,
+    ) -> impl Iterator<Item = &'a Resource> + 'a {
+        self.resources().filter(move |res| pred(res))
+    }
+
+    pub fn default_unlocked(&self) -> Vec<&Resource> {
+        self.resources_where(|res| res.unlocked_by_default())
+            .collect()
+    }
+
+    pub fn initially_hidden(&self) -> Vec<&Resource> {
+        self.resources_where(|res| !res.unlocked_by_default())
+            .collect()
+    }
+
+    /// Marks a hidden resource as discovered, such as when it is first produced. Returns whether
+    /// the resource was newly discovered.
+    pub fn discover(&mut self, id: u64) -> bool {
+        match self.resources.get(&id) {
+            Some(resource) if !resource.unlocked_by_default() => self.discovered.insert(id),
+            _ => false,
+        }
+    }
+
+    pub fn is_visible(&self, id: u64) -> bool {
+        self.resources.get(&id).is_some_and(|res| {
+            res.unlocked_by_default() || self.discovered.contains(&id)
+        })
+    }
+
+    /// Gets the resources unlocked by default along with every discovered resource
+    pub fn visible_resources(&self) -> Vec<&Resource> {
+        self.resources_where(move |res| self.is_visible(res.id()))
+            .collect()
+    }
+
+    pub fn resources_with_tag_count(&self, count: usize) -> Vec<&Resource> {
+        self.resources_with_tag_count_between(count, count)
+    }
+
+    /// Gets the resources with at least `min` and at most `max` tags
+    pub fn resources_with_tag_count_between(&self, min: usize, max: usize) -> Vec<&Resource> {
+        self.resources_where(move |res| (min..=max).contains(&res.tags().len()))
+            .collect()
+    }
+}
+
+impl Debug for ResourceManager {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ResourceManager {{")?;
+        write!(
+            f,
+            "{}",
+            self.resources()
+                .map(|res| res.name().clone())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )?;
+        write!(f, "}}")
+    }
+}
+
+fn is_base_metal(resource: &Resource) -> bool {
+    resource.contains_all_tags(&[ResourceTag::Base, ResourceTag::Metal])
+}
+
+/// Builds a [`Resource`] one named field at a time. Only the name is required; colors default to
+/// the default colors and everything else to empty.
+#[derive(Debug, Clone)]
+pub struct ResourceBuilder {
+    name: Option<String>,
+    description: String,
+    symbol: Option<String>,
+    icon: PathBuf,
+    fg_color: Color,
+    bg_color: Color,
+    tags: Vec<ResourceTag>,
+    tier: u8,
+    base_value: f64,
+    stack_size: Option<u32>,
+}
+
+impl ResourceBuilder {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            description: String::new(),
+            symbol: None,
+            icon: PathBuf::new(),
+            fg_color: DEFAULT_FG_COLOR,
+            bg_color: DEFAULT_BG_COLOR,
+            tags: vec![],
+            tier: 0,
+            base_value: 0.0,
+            stack_size: None,
+        }
+    }
+
+    pub fn name<S: AsRef<str>>(mut self, name: S) -> Self {
+        self.name = Some(name.as_ref().to_string());
+        self
+    }
+
+    pub fn description<S: AsRef<str>>(mut self, description: S) -> Self {
+        self.description = description.as_ref().to_string();
+        self
+    }
+
+    pub fn symbol<S: AsRef<str>>(mut self, symbol: S) -> Self {
+        self.symbol = Some(symbol.as_ref().to_string());
+        self
+    }
+
+    pub fn icon<P: AsRef<Path>>(mut self, icon: P) -> Self {
+        self.icon = icon.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn fg_color(mut self, color: Color) -> Self {
+        self.fg_color = color;
+        self
+    }
+
+    pub fn bg_color(mut self, color: Color) -> Self {
+        self.bg_color = color;
+        self
+    }
+
+    /// Adds a tag, in addition to any already added
+    pub fn tag(mut self, tag: ResourceTag) -> Self {
+        self.tags.push(tag);
+        self
+    }
+
+    /// Adds several tags, in addition to any already added
+    pub fn tags<I: IntoIterator<Item = ResourceTag>>(mut self, tags: I) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+
+    pub fn tier(mut self, tier: u8) -> Self {
+        self.tier = tier;
+        self
+    }
+
+    pub fn base_value(mut self, base_value: f64) -> Self {
+        self.base_value = base_value;
+        self
+    }
+
+    pub fn stack_size(mut self, stack_size: u32) -> Self {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    pub fn build(self) -> Result<Resource, String> {
+        let name = self
+            .name
+            .ok_or_else(|| "A resource needs a name".to_string())?;
+        let mut resource = Resource::new(
+            name,
+            self.description,
+            self.icon,
+            self.fg_color,
+            self.bg_color,
+            &self.tags,
+        )
+        .with_tier(self.tier)
+        .with_base_value(self.base_value);
+        resource.symbol = self.symbol;
+        resource.stack_size = self.stack_size;
+        Ok(resource)
+    }
+}
+
+impl Default for ResourceBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn default_fg_color() -> Color {
+    DEFAULT_FG_COLOR
+}
+
+fn default_bg_color() -> Color {
+    DEFAULT_BG_COLOR
+}
+
+/// A resource as written in a resources file. Colors are usually hex strings such as `"#B87333"`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceDef {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub icon: PathBuf,
+    #[serde(
+        default = "default_fg_color",
+        with = "crate::production::color::serde_color"
+    )]
+    pub fg_color: Color,
+    #[serde(
+        default = "default_bg_color",
+        with = "crate::production::color::serde_color"
+    )]
+    pub bg_color: Color,
+    #[serde(default)]
+    pub tags: Vec<ResourceTag>,
+    #[serde(default)]
+    pub tier: u8,
+    #[serde(default)]
+    pub base_value: f64,
+    #[serde(default)]
+    pub stack_size: Option<u32>,
+}
+
+impl From<ResourceDef> for Resource {
+    fn from(def: ResourceDef) -> Self {
+        let mut resource = Resource::new(
+            def.name,
+            def.description,
+            def.icon,
+            def.fg_color,
+            def.bg_color,
+            &def.tags,
+        )
+        .with_tier(def.tier)
+        .with_base_value(def.base_value);
+        resource.symbol = def.symbol;
+        resource.stack_size = def.stack_size;
+        resource
+    }
+}
+
+/// A base metal for [`setup_resource_manager_with`] to add along with its derived resources
+#[derive(Debug, Clone)]
+pub struct BaseMetalSpec {
+    pub name: String,
+    pub description: String,
+    pub symbol: Option<String>,
+    pub fg_color: Color,
+    pub bg_color: Color,
+}
+
+impl BaseMetalSpec {
+    pub fn new<S1: AsRef<str>, S2: AsRef<str>>(name: S1, description: S2) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+            description: description.as_ref().to_string(),
+            symbol: None,
+            fg_color: Color::WHITE,
+            bg_color: Color::BLACK,
+        }
+    }
+
+    pub fn with_symbol<S: AsRef<str>>(mut self, symbol: S) -> Self {
+        self.symbol = Some(symbol.as_ref().to_string());
+        self
+    }
+
+    pub fn with_colors(mut self, fg_color: Color, bg_color: Color) -> Self {
+        self.fg_color = fg_color;
+        self.bg_color = bg_color;
+        self
+    }
+}
+
+/// The base metals used by [`setup_resource_manager`]
+pub fn default_base_metals() -> Vec<BaseMetalSpec> {
+    vec![
+        BaseMetalSpec::new("Iron", "A common, sturdy metal").with_symbol("Fe"),
+        BaseMetalSpec::new("Copper", "A soft, conductive metal").with_symbol("Cu"),
+        BaseMetalSpec::new("Gold", "A rare and precious metal").with_symbol("Au"),
+    ]
+}
+
+pub fn setup_resource_manager(manager: &mut ResourceManager) -> Result<(), String> {
+    setup_resource_manager_with(manager, &default_base_metals())
+}
+
+pub fn setup_resource_manager_with(
+    manager: &mut ResourceManager,
+    base_metals: &[BaseMetalSpec],
+) -> Result<(), String> {
+    for metal in base_metals {
+        let mut resource = Resource::new(
+            &metal.name,
+            &metal.description,
+            "",
+            metal.fg_color,
+            metal.bg_color,
+            &[ResourceTag::Metal, ResourceTag::Base],
+        );
+        resource.symbol = metal.symbol.clone();
+        manager.add_resource(resource).map_err(|e| e.to_string())?;
+    }
+    add_default_transformers(manager)
+}
+
+/// Sets up a resource manager with the resources defined in a resources file instead of the
+/// default base metals
+pub fn setup_resource_manager_from<P: AsRef<Path>>(
+    manager: &mut ResourceManager,
+    path: P,
+) -> Result<(), String> {
+    manager.load_resources(path)?;
+    add_default_transformers(manager)
+}
+
+/// Adds the transformers deriving the ingots, plates, ores and wires of base metals
+fn add_default_transformers(manager: &mut ResourceManager) -> Result<(), String> {
+    // transformers first
+    manager.add_named_processed_transformer(
+        "Ingot",
+        "{name} Ingot",
+        TransformerBuilder::new()
+            .when(is_base_metal)
+            .produce(|resource| {
+                Resource::new(
+                    format!("{} Ingot", resource.name()),
+                    format!("A pure form of {} in a convenient bar form", resource.name),
+                    "",
+                    resource.fg_color,
+                    resource.bg_color,
+                    &[ResourceTag::Metal, ResourceTag::Ingot],
+                )
+                .with_tier(resource.tier.saturating_add(1))
+            })
+            .build(),
+    )?;
+    manager.add_named_processed_transformer(
+        "Plate",
+        "{name} Plate",
+        TransformerBuilder::new()
+            .when(is_base_metal)
+            .produce(|resource| {
+                Resource::new(
+                    format!("{} Plate", resource.name()),
+                    format!("{} flattened to the MAXIMUM extent", resource.name),
+                    "",
+                    resource.fg_color,
+                    resource.bg_color,
+                    &[ResourceTag::Metal],
+                )
+            })
+            .build(),
+    )?;
+    manager.add_named_processed_transformer(
+        "Ore",
+        "{name} Ore",
+        TransformerBuilder::new()
+            .when(is_base_metal)
+            .produce(|resource| {
+                Resource::new(
+                    format!("{} Ore", resource.name()),
+                    format!("The ore form of {}", resource.name),
+                    "",
+                    resource.fg_color,
+                    resource.bg_color,
+                    &[ResourceTag::Metal, ResourceTag::Ore],
+                )
+            })
+            .build(),
+    )?;
+    manager.add_named_processed_transformer(
+        "Wire",
+        "{name} Wire",
+        TransformerBuilder::new()
+            .when(is_base_metal)
+            .produce(|resource| {
+                Resource::new(
+                    format!("{} Wire", resource.name()),
+                    format!("{} thin and noodly, just the way I like it", resource.name),
+                    "",
+                    resource.fg_color,
+                    resource.bg_color,
+                    &[ResourceTag::Metal],
+                )
+            })
+            .build(),
+    )?;
+    /*
+    manager.add_processed_transformer(|resource| {
+        ProcessedResource::new(None, Some("Ore"), resource.clone(), "The ore form!", "")
+    });
+    manager.add_processed_transformer(|resource| {
+        ProcessedResource::new(
+            None,
+            Some("Plate"),
+            resource.clone(),
+            "Flattened to the MAXIMUM extent",
+            "",
+        )
+    });
+
+     */
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transformations_work() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        println!("{:?}", manager);
+        assert!(
+            manager.resource_by_name("Iron").is_some(),
+            "Iron wasn't created"
+        );
+        assert!(manager.resource_by_name("Iron Plate").is_some());
+        assert!(manager.resource_by_name("Iron Ingot").is_some());
+    }
+
+    #[test]
+    fn setup_transformer_patterns_are_listed() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let patterns = manager.derived_name_patterns();
+        let expected = ["Ingot", "Plate", "Ore", "Wire"]
+            .iter()
+            .map(|suffix| (suffix.to_string(), format!("{{name}} {}", suffix)))
+            .collect::<Vec<_>>();
+        assert_eq!(patterns, expected);
+    }
+
+    #[test]
+    fn custom_base_metal_gets_derived_resources() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager_with(
+            &mut manager,
+            &[BaseMetalSpec::new("Tin", "A soft, silvery metal").with_symbol("Sn")],
+        )
+        .unwrap();
+        assert_eq!(manager.resource_by_name("Tin").unwrap().symbol(), Some("Sn"));
+        for derived in &["Tin Ingot", "Tin Plate", "Tin Ore", "Tin Wire"] {
+            assert!(manager.resource_by_name(derived).is_some(), "{} wasn't created", derived);
+        }
+        assert!(manager.resource_by_name("Iron").is_none());
+    }
+
+    #[test]
+    fn resources_where_filters_on_predicate() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mut names = manager
+            .resources_where(|res| res.contains_tag(&ResourceTag::Ingot) && res.name().starts_with('I'))
+            .map(|res| res.name().as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, vec!["Iron Ingot"]);
+        assert!(manager.resources_where(|_| false).next().is_none());
+
+        let long_metals =
+            |res: &Resource| res.contains_tag(&ResourceTag::Metal) && res.name().len() > 10;
+        let mut names = manager
+            .resources_where(long_metals)
+            .map(|res| res.name().as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, vec!["Copper Ingot", "Copper Plate", "Copper Wire"]);
+        assert_eq!(manager.resources_where(long_metals).take(2).count(), 2);
+    }
+
+    fn enchant(resource: &Resource) -> Option<Resource> {
+        if resource.contains_tag(&ResourceTag::Ingot) {
             Some(Resource::new(
-                format!("{} Plate", resource.name()),
-                format!("{} flattened to the MAXIMUM extent", resource.name),
+                format!("Enchanted {}", resource.name()),
+                "It glows faintly",
                 "",
                 resource.fg_color,
                 resource.bg_color,
                 &[ResourceTag::Metal],
             ))
-        } else {
-            None
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn self_matching_transformer_is_an_error() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let count = manager.resources().count();
+        let polish = |resource: &Resource| {
+            Some(Resource::new(
+                format!("Polished {}", resource.name()),
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[],
+            ))
+        };
+        assert_eq!(
+            manager.add_processed_transformer(polish),
+            Err("transformer produced a cyclic resource".to_string())
+        );
+        assert_eq!(manager.resources().count(), count);
+        assert!(manager.resource_by_name("Polished Iron").is_none());
+
+        let mut manager = ResourceManager::new();
+        manager.add_processed_transformer(polish).unwrap();
+        assert_eq!(
+            manager.add_resource(Resource::new(
+                "Tin",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[]
+            )),
+            Err(ResourceError::CyclicDerivation)
+        );
+        assert_eq!(manager.resources().count(), 0);
+        let tin = Resource::new("Tin", "", "", Color::WHITE, Color::BLACK, &[]);
+        assert_eq!(manager.add_resource(tin), Err(ResourceError::CyclicDerivation));
+    }
+
+    #[test]
+    fn transformer_applies_to_derived_when_opted_in() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        manager
+            .add_processed_transformer_with(enchant, true)
+            .unwrap();
+        let enchanted = manager.resource_by_name("Enchanted Iron Ingot").unwrap();
+        assert!(enchanted.is_derived());
+        assert!(!manager.resource_by_name("Iron").unwrap().is_derived());
+
+        manager
+            .add_resource(Resource::new(
+                "Tin",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[ResourceTag::Metal, ResourceTag::Base],
+            ))
+            .unwrap();
+        assert!(manager.resource_by_name("Enchanted Tin Ingot").is_some());
+    }
+
+    #[test]
+    fn transformer_skips_derived_when_opted_out() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        manager
+            .add_processed_transformer_with(enchant, false)
+            .unwrap();
+        assert!(manager.resource_by_name("Enchanted Iron Ingot").is_none());
+
+        manager
+            .add_resource(Resource::new(
+                "Tin",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[ResourceTag::Metal, ResourceTag::Base],
+            ))
+            .unwrap();
+        assert!(manager.resource_by_name("Enchanted Tin Ingot").is_none());
+
+        manager
+            .add_resource(Resource::new(
+                "Bronze Ingot",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[ResourceTag::Metal, ResourceTag::Ingot],
+            ))
+            .unwrap();
+        assert!(manager.resource_by_name("Enchanted Bronze Ingot").is_some());
+    }
+
+    #[test]
+    fn resources_filtered_by_tag_count() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let two_tags = manager.resources_with_tag_count(2);
+        for name in &["Iron", "Copper", "Gold"] {
+            assert!(two_tags.iter().any(|res| res.name() == name));
+        }
+        assert!(two_tags.iter().all(|res| res.name() != "Iron Plate"));
+        assert!(manager.resources_with_tag_count(0).is_empty());
+
+        let one_or_two = manager.resources_with_tag_count_between(1, 2);
+        assert_eq!(one_or_two.len(), manager.resources().count());
+    }
+
+    #[test]
+    fn hidden_resource_visible_once_discovered() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mithril = manager
+            .add_resource(
+                Resource::new("Mithril", "", "", Color::WHITE, Color::BLACK, &[])
+                    .with_unlocked_by_default(false),
+            )
+            .unwrap();
+        let hidden = manager.initially_hidden();
+        assert_eq!(hidden.len(), 1);
+        assert_eq!(hidden[0].name(), "Mithril");
+        assert!(manager
+            .default_unlocked()
+            .iter()
+            .all(|res| res.id() != mithril));
+        assert!(manager
+            .visible_resources()
+            .iter()
+            .all(|res| res.id() != mithril));
+
+        assert!(manager.discover(mithril));
+        assert!(!manager.discover(mithril));
+        assert!(manager
+            .visible_resources()
+            .iter()
+            .any(|res| res.id() == mithril));
+        assert!(manager
+            .default_unlocked()
+            .iter()
+            .all(|res| res.id() != mithril));
+    }
+
+    #[test]
+    fn tag_limit_blocks_extra_resources() {
+        let mut manager = ResourceManager::new();
+        manager.set_tag_limit(ResourceTag::Ingot, 2);
+        let ingot = |name| {
+            Resource::new(
+                name,
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[ResourceTag::Ingot],
+            )
+        };
+        manager.add_resource(ingot("Tin Ingot")).unwrap();
+        manager.add_resource(ingot("Lead Ingot")).unwrap();
+        assert_eq!(
+            manager.add_resource(ingot("Zinc Ingot")),
+            Err(ResourceError::TagLimitExceeded {
+                tag: ResourceTag::Ingot,
+                limit: 2
+            })
+        );
+        assert!(manager.resource_by_name("Zinc Ingot").is_none());
+
+        let mut manager = ResourceManager::new();
+        manager.set_tag_limit(ResourceTag::Ingot, 2);
+        assert!(setup_resource_manager(&mut manager).is_err());
+        // The ingot transformer failed on the third metal, so none of its ingots are kept
+        assert!(manager.resources_with_tag(&ResourceTag::Ingot).is_empty());
+    }
+
+    #[test]
+    fn implied_tags_are_found_by_queries() {
+        let mut manager = ResourceManager::new();
+        let alloy = ResourceTag::Custom("Alloy".to_string());
+        let tin = manager
+            .add_resource(Resource::new(
+                "Tin Ingot",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[ResourceTag::Ingot],
+            ))
+            .unwrap();
+        manager
+            .add_tag_implication(ResourceTag::Ingot, ResourceTag::Metal)
+            .unwrap();
+        manager
+            .add_tag_implication(alloy.clone(), ResourceTag::Ingot)
+            .unwrap();
+        let bronze = manager
+            .add_resource(Resource::new(
+                "Bronze",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                std::slice::from_ref(&alloy),
+            ))
+            .unwrap();
+
+        let mut metals = manager
+            .resources_with_tag(&ResourceTag::Metal)
+            .iter()
+            .map(|res| res.id())
+            .collect::<Vec<_>>();
+        metals.sort_unstable();
+        assert_eq!(metals, vec![tin, bronze]);
+        assert!(manager
+            .resource_by_id(bronze)
+            .unwrap()
+            .contains_all_tags(&[ResourceTag::Ingot, ResourceTag::Metal]));
+
+        assert!(manager
+            .add_tag_implication(ResourceTag::Metal, alloy)
+            .is_err());
+        assert!(manager
+            .add_tag_implication(ResourceTag::Ore, ResourceTag::Ore)
+            .is_err());
+    }
+
+    #[test]
+    fn resources_sorted_by_id_and_name() {
+        let mut manager = ResourceManager::new();
+        for name in ["Gold", "Copper", "Iron"].iter() {
+            manager
+                .add_resource(Resource::new(
+                    *name,
+                    "",
+                    "",
+                    Color::WHITE,
+                    Color::BLACK,
+                    &[],
+                ))
+                .unwrap();
+        }
+        let names = |order| {
+            manager
+                .resources_sorted(order)
+                .iter()
+                .map(|res| res.name().clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(ResourceOrder::Id), vec!["Gold", "Copper", "Iron"]);
+        assert_eq!(names(ResourceOrder::Name), vec!["Copper", "Gold", "Iron"]);
+        assert_eq!(
+            format!("{:?}", manager),
+            "ResourceManager {Gold, Copper, Iron}"
+        );
+    }
+
+    #[test]
+    fn tag_index_matches_scan() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        manager
+            .add_tag_implication(ResourceTag::Ingot, ResourceTag::Custom("Bar".to_string()))
+            .unwrap();
+        manager.remove_resource_by_name("Copper Ore").unwrap();
+        let scan = |tags: &[ResourceTag]| {
+            manager
+                .resources()
+                .filter(|res| res.contains_all_tags(tags))
+                .map(|res| res.id())
+                .collect::<Vec<_>>()
+        };
+        let ids =
+            |resources: Vec<&Resource>| resources.iter().map(|res| res.id()).collect::<Vec<_>>();
+
+        for tag in [
+            ResourceTag::Base,
+            ResourceTag::Metal,
+            ResourceTag::Ore,
+            ResourceTag::Ingot,
+            ResourceTag::Custom("Bar".to_string()),
+            ResourceTag::Custom("Gem".to_string()),
+        ]
+        .iter()
+        {
+            assert_eq!(ids(manager.resources_with_tag(tag)), scan(std::slice::from_ref(tag)));
+        }
+        for tags in [
+            vec![ResourceTag::Metal, ResourceTag::Ore],
+            vec![ResourceTag::Custom("Bar".to_string()), ResourceTag::Metal],
+            vec![ResourceTag::Base, ResourceTag::Ingot],
+            vec![],
+        ]
+        .iter()
+        {
+            assert_eq!(ids(manager.resources_with_tags(tags)), scan(tags));
+        }
+        assert_eq!(manager.resources_with_tag(&ResourceTag::Ore).len(), 2);
+    }
+
+    #[test]
+    fn any_tag_lists_each_match_once() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let any = manager
+            .resources_with_any_tag(&[ResourceTag::Ore, ResourceTag::Ingot, ResourceTag::Metal])
+            .iter()
+            .map(|res| res.id())
+            .collect::<Vec<_>>();
+        let metals = manager
+            .resources_with_tag(&ResourceTag::Metal)
+            .iter()
+            .map(|res| res.id())
+            .collect::<Vec<_>>();
+        // Every ore and ingot is also a metal, so each appears once in the metals' order
+        assert_eq!(any, metals);
+
+        let base_or_ore = manager.resources_with_any_tag(&[ResourceTag::Base, ResourceTag::Ore]);
+        assert_eq!(base_or_ore.len(), 6);
+        assert!(base_or_ore
+            .windows(2)
+            .all(|pair| pair[0].id() < pair[1].id()));
+        assert!(manager.resources_with_any_tag(&[]).is_empty());
+    }
+
+    #[test]
+    fn excluded_tags_knock_out_matches() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let names = |resources: Vec<&Resource>| {
+            let mut names = resources
+                .iter()
+                .map(|res| res.name().clone())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            names(manager.resources_matching(
+                &[ResourceTag::Metal],
+                &[ResourceTag::Ingot, ResourceTag::Ore, ResourceTag::Base]
+            )),
+            vec![
+                "Copper Plate",
+                "Copper Wire",
+                "Gold Plate",
+                "Gold Wire",
+                "Iron Plate",
+                "Iron Wire"
+            ]
+        );
+        assert!(manager
+            .resources_without_tag(&ResourceTag::Metal)
+            .is_empty());
+        assert_eq!(
+            manager.resources_without_tag(&ResourceTag::Ingot).len(),
+            manager.resources().count() - 3
+        );
+
+        // Implied tags are excluded too
+        manager
+            .add_tag_implication(ResourceTag::Ore, ResourceTag::Custom("Raw".to_string()))
+            .unwrap();
+        assert_eq!(
+            names(manager.resources_matching(
+                &[ResourceTag::Metal],
+                &[ResourceTag::Custom("Raw".to_string()), ResourceTag::Base]
+            )),
+            names(manager.resources_matching(
+                &[ResourceTag::Metal],
+                &[ResourceTag::Ore, ResourceTag::Base]
+            ))
+        );
+    }
+
+    #[test]
+    fn tags_round_trip_through_json() {
+        let tags = [
+            (ResourceTag::Base, "\"Base\""),
+            (ResourceTag::Metal, "\"Metal\""),
+            (ResourceTag::Ore, "\"Ore\""),
+            (ResourceTag::Ingot, "\"Ingot\""),
+        ];
+        for (tag, json) in tags.iter() {
+            assert_eq!(&serde_json::to_string(tag).unwrap(), json);
+            assert_eq!(&serde_json::from_str::<ResourceTag>(json).unwrap(), tag);
         }
-    })?;
-    manager.add_processed_transformer(|resource| {
-        if resource.contains_all_tags(&[ResourceTag::Base, ResourceTag::Metal]) {
-            Some(Resource::new(
-                format!("{} Ore", resource.name()),
-                format!("The ore form of {}", resource.name),
+    }
+
+    #[test]
+    fn tags_round_trip_through_strings() {
+        for tag in [
+            ResourceTag::Base,
+            ResourceTag::Metal,
+            ResourceTag::Ore,
+            ResourceTag::Ingot,
+        ]
+        .iter()
+        {
+            assert_eq!(tag.to_string().parse::<ResourceTag>().as_ref(), Ok(tag));
+        }
+        assert_eq!("Metal".parse(), Ok(ResourceTag::Metal));
+        assert_eq!(
+            "metal".parse(),
+            Ok(ResourceTag::Custom("metal".to_string()))
+        );
+        assert_eq!(
+            " ".parse::<ResourceTag>(),
+            Err(TagParseError(" ".to_string()))
+        );
+    }
+
+    #[test]
+    fn custom_tags_are_distinct_from_built_in_ones() {
+        let fluid = ResourceTag::Custom("Fluid".to_string());
+        assert_eq!(serde_json::to_string(&fluid).unwrap(), "\"Fluid\"");
+        assert_eq!(
+            serde_json::from_str::<ResourceTag>("\"Fluid\"").unwrap(),
+            fluid
+        );
+        assert_ne!(ResourceTag::Custom("Metal".to_string()), ResourceTag::Metal);
+
+        let mut manager = ResourceManager::new();
+        let water = manager
+            .add_resource(Resource::new(
+                "Water",
                 "",
-                resource.fg_color,
-                resource.bg_color,
-                &[ResourceTag::Metal, ResourceTag::Ore],
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[fluid.clone(), ResourceTag::Base],
             ))
-        } else {
-            None
+            .unwrap();
+        let ids = |tag: &ResourceTag| {
+            manager
+                .resources_with_tag(tag)
+                .iter()
+                .map(|res| res.id())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&fluid), vec![water]);
+        assert!(manager
+            .resource_by_id(water)
+            .unwrap()
+            .contains_all_tags(&[fluid, ResourceTag::Base]));
+        assert!(ids(&ResourceTag::Custom("Base".to_string())).is_empty());
+    }
+
+    #[test]
+    fn missing_resource_error_carries_name() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        assert_eq!(manager.resource_by_name_res("Iron").unwrap().name(), "Iron");
+        assert_eq!(
+            manager.resource_by_name_res("Unobtainium").unwrap_err(),
+            ResourceError::NotFound("Unobtainium".to_string())
+        );
+        assert_eq!(
+            manager.resource_by_id_res(9999).unwrap_err(),
+            ResourceError::NotFound("#9999".to_string())
+        );
+    }
+
+    #[test]
+    fn removed_ids_are_not_reused() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let count = manager.resources().count();
+        let iron_ore = manager.resource_id_by_name("Iron Ore").unwrap();
+
+        let removed = manager.remove_resource(iron_ore).unwrap();
+        assert_eq!(removed.name(), "Iron Ore");
+        assert!(manager.remove_resource(iron_ore).is_none());
+        assert_eq!(manager.resources().count(), count - 1);
+        assert!(manager.resource_by_name("Iron Ore").is_none());
+        assert!(manager.prefix_search("Iron O").is_empty());
+
+        let tin = manager
+            .add_resource(Resource::new(
+                "Tin",
+                "",
+                "",
+                Color::WHITE,
+                Color::BLACK,
+                &[],
+            ))
+            .unwrap();
+        assert!(tin > iron_ore);
+        assert!(manager.resource_by_id(iron_ore).is_none());
+
+        assert_eq!(manager.remove_resource_by_name("Tin").unwrap().id(), tin);
+        assert!(manager.remove_resource_by_name("Tin").is_none());
+    }
+
+    #[test]
+    fn autocolor_only_colors_default_resources() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let red = Color::from_rgb(1.0, 0.0, 0.0);
+        manager
+            .add_resource(Resource::new("Ruby", "", "", red, Color::BLACK, &[]))
+            .unwrap();
+        manager.autocolor(ColorScheme::Dark);
+
+        let iron_ore = manager.resource_by_name("Iron Ore").unwrap();
+        let gold_ore = manager.resource_by_name("Gold Ore").unwrap();
+        assert!(!iron_ore.has_default_colors());
+        assert_ne!(iron_ore.bg_color(), gold_ore.bg_color());
+        // ore family colors are reddish-orange, with more red than blue
+        for ore in &[iron_ore, gold_ore] {
+            assert!(ore.bg_color().r > ore.bg_color().b);
         }
-    })?;
-    manager.add_processed_transformer(|resource| {
-        if resource.contains_all_tags(&[ResourceTag::Base, ResourceTag::Metal]) {
+        let iron_ingot = manager.resource_by_name("Iron Ingot").unwrap();
+        assert!(iron_ingot.bg_color().b > iron_ingot.bg_color().r);
+
+        let ruby = manager.resource_by_name("Ruby").unwrap();
+        assert_eq!(*ruby.fg_color(), red);
+        assert_eq!(*ruby.bg_color(), Color::BLACK);
+    }
+
+    #[test]
+    fn prefix_search_over_many_resources() {
+        let mut manager = ResourceManager::new();
+        for i in 0..3000 {
+            manager
+                .add_resource(Resource::new(
+                    format!("Resource {}", i),
+                    "",
+                    "",
+                    Color::WHITE,
+                    Color::BLACK,
+                    &[],
+                ))
+                .unwrap();
+        }
+        let found = manager.prefix_search("Resource 12");
+        // "Resource 12", "Resource 120" to "Resource 129" and "Resource 1200" to "Resource 1299"
+        assert_eq!(found.len(), 111);
+        assert!(found
+            .iter()
+            .all(|res| res.name().starts_with("Resource 12")));
+        assert_eq!(found[0].name(), "Resource 12");
+        assert_eq!(manager.prefix_search("").len(), 3000);
+        assert!(manager.prefix_search("Iron").is_empty());
+        // "Resource 299" and "Resource 2990" to "Resource 2999"
+        assert_eq!(manager.prefix_search("Resource 299").len(), 11);
+    }
+
+    #[test]
+    fn name_lookups_over_many_resources() {
+        let mut manager = ResourceManager::new();
+        for i in 0..5000 {
+            manager
+                .add_resource(Resource::new(
+                    format!("Resource {}", i),
+                    "",
+                    "",
+                    Color::WHITE,
+                    Color::BLACK,
+                    &[],
+                ))
+                .unwrap();
+        }
+        for i in 0..5000 {
+            let name = format!("Resource {}", i);
+            let id = manager.resource_id_by_name(&name).unwrap();
+            assert_eq!(manager.resource_by_name(&name).unwrap().id(), id);
+            assert_eq!(manager.resource_by_id(id).unwrap().name(), &name);
+        }
+        // Every name is looked up through the index rather than by scanning the resources
+        assert_eq!(manager.names.len(), 5000);
+
+        let removed = manager.remove_resource_by_name("Resource 42").unwrap();
+        assert!(manager.resource_by_name("Resource 42").is_none());
+        assert!(manager.resource_by_id(removed.id()).is_none());
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let resource = Resource::new("", "", "", Color::BLACK, Color::BLACK, &[]);
+        assert_eq!(
+            resource.validate(),
+            Err(vec![
+                "Resource has an empty name".to_string(),
+                " has the same foreground and background color".to_string(),
+            ])
+        );
+
+        let ingot = Resource::new(
+            "Tin Ingot",
+            "",
+            "",
+            Color::WHITE,
+            Color::BLACK,
+            &[ResourceTag::Ingot],
+        );
+        assert_eq!(
+            ingot.validate(),
+            Err(vec!["Tin Ingot is an Ingot but not a Metal".to_string()])
+        );
+        let rules = ValidationRules {
+            ingot_implies_metal: false,
+            ..Default::default()
+        };
+        assert!(ingot.validate_with(&rules).is_ok());
+
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        assert!(manager.resources().all(|res| res.validate().is_ok()));
+        manager.add_resource(ingot.clone()).unwrap();
+        manager.set_strict_validation(Some(ValidationRules::default()));
+        assert!(manager.add_resource(resource).is_err());
+        let mut lead = ingot;
+        lead.name = "Lead Ingot".to_string();
+        assert!(manager.add_resource(lead).is_err());
+    }
+
+    #[test]
+    fn base_metals_have_distinct_symbols() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let symbol = |name| manager.resource_by_name(name).unwrap().symbol();
+        assert_eq!(symbol("Iron"), Some("Fe"));
+        assert_eq!(symbol("Copper"), Some("Cu"));
+        assert_eq!(symbol("Gold"), Some("Au"));
+        assert_eq!(symbol("Iron Ingot"), None);
+    }
+
+    #[test]
+    fn resources_load_from_file() {
+        let mut defaults = ResourceManager::new();
+        setup_resource_manager(&mut defaults).unwrap();
+        let mut loaded = ResourceManager::new();
+        setup_resource_manager_from(&mut loaded, "configurations/resources.json").unwrap();
+        let summary = |manager: &ResourceManager| {
+            manager
+                .resources()
+                .map(|res| {
+                    (
+                        res.name().clone(),
+                        res.symbol().map(String::from),
+                        res.tags().clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(summary(&loaded), summary(&defaults));
+
+        let dir = crate::test_util::TempDir::new("resources");
+        let path = dir.join("resources.json");
+        std::fs::write(
+            &path,
+            r##"[
+                { "name": "Tin", "fg_color": "#B87333", "tags": ["Metal", "Fuel"] },
+                { "name": "Tin" }
+            ]"##,
+        )
+        .unwrap();
+        let mut manager = ResourceManager::new();
+        let result = manager.load_resources(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.unwrap_err().starts_with("Couldn't add Tin"));
+        let tin = manager.resource_by_name("Tin").unwrap();
+        assert_eq!(
+            tin.fg_color(),
+            &crate::production::color::from_hex("#B87333").unwrap()
+        );
+        assert_eq!(tin.bg_color(), &DEFAULT_BG_COLOR);
+        assert!(tin.contains_tag(&ResourceTag::Custom("Fuel".to_string())));
+    }
+
+    #[test]
+    fn icons_are_validated() {
+        let dir = crate::test_util::TempDir::new("icons");
+        std::fs::write(dir.join("iron.png"), b"\x89PNG\r\n\x1a\nrest of the image").unwrap();
+        std::fs::write(dir.join("notes.png"), "not an image").unwrap();
+
+        let mut manager = ResourceManager::new();
+        let mut add = |name: &str, icon: &str| {
+            manager
+                .add_resource(Resource::new(
+                    name,
+                    "",
+                    icon,
+                    Color::WHITE,
+                    Color::BLACK,
+                    &[],
+                ))
+                .unwrap()
+        };
+        add("Iron", "iron.png");
+        let copper = add("Copper", "copper.png");
+        add("Gold", "");
+        let notes = add("Notes", "notes.png");
+
+        let errors = manager.validate_icons(dir.path());
+        assert_eq!(
+            errors,
+            vec![
+                (copper, IconError::Missing(dir.join("copper.png"))),
+                (notes, IconError::UnsupportedFormat(dir.join("notes.png"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn builder_needs_only_a_name() {
+        let tin = ResourceBuilder::new().name("Tin").build().unwrap();
+        assert_eq!(tin.name(), "Tin");
+        assert_eq!(tin.description(), "");
+        assert_eq!(tin.symbol(), None);
+        assert!(tin.base_icon().as_os_str().is_empty());
+        assert!(tin.has_default_colors());
+        assert!(tin.tags().is_empty());
+
+        assert!(ResourceBuilder::new()
+            .description("Nameless")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn builder_sets_every_field() {
+        let copper = ResourceBuilder::new()
+            .name("Copper")
+            .description("A soft, conductive metal")
+            .symbol("Cu")
+            .icon("copper.png")
+            .fg_color(Color::BLACK)
+            .bg_color(Color::WHITE)
+            .tag(ResourceTag::Metal)
+            .tags(vec![
+                ResourceTag::Base,
+                ResourceTag::Custom("Conductor".to_string()),
+            ])
+            .build()
+            .unwrap();
+        assert_eq!(copper.name(), "Copper");
+        assert_eq!(copper.description(), "A soft, conductive metal");
+        assert_eq!(copper.symbol(), Some("Cu"));
+        assert_eq!(copper.base_icon(), Path::new("copper.png"));
+        assert_eq!(copper.fg_color(), &Color::BLACK);
+        assert_eq!(copper.bg_color(), &Color::WHITE);
+        assert_eq!(
+            copper.tags(),
+            &vec![
+                ResourceTag::Metal,
+                ResourceTag::Base,
+                ResourceTag::Custom("Conductor".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn resources_compare_by_content() {
+        let mut first = ResourceManager::new();
+        let mut second = ResourceManager::new();
+        setup_resource_manager(&mut first).unwrap();
+        second
+            .add_resource(ResourceBuilder::new().name("Tin").build().unwrap())
+            .unwrap();
+        setup_resource_manager(&mut second).unwrap();
+
+        let iron = first.resource_by_name("Iron").unwrap();
+        let other_iron = second.resource_by_name("Iron").unwrap();
+        assert_ne!(iron.id(), other_iron.id());
+        assert_ne!(iron, other_iron);
+        assert!(iron.same_content(other_iron));
+        assert_eq!(iron, &iron.clone());
+
+        let mut gold = first.resource_by_name("Gold").unwrap().clone();
+        assert!(!iron.same_content(&gold));
+        gold.fg_color.r = f32::NAN;
+        assert_ne!(gold, gold.clone());
+    }
+
+    #[test]
+    fn updates_keep_lookups_consistent() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let iron = manager.resource_id_by_name("Iron").unwrap();
+        let fuel = ResourceTag::Custom("Fuel".to_string());
+
+        manager
+            .update_resource(iron, |resource| {
+                resource.tags.retain(|tag| *tag != ResourceTag::Base);
+                resource.tags.push(fuel.clone());
+                resource.description = "Burns, somehow".to_string();
+            })
+            .unwrap();
+        assert_eq!(manager.resources_with_tag(&fuel)[0].id(), iron);
+        assert!(manager
+            .resources_with_tag(&ResourceTag::Base)
+            .iter()
+            .all(|res| res.id() != iron));
+        assert_eq!(
+            manager.resource_by_id(iron).unwrap().description(),
+            "Burns, somehow"
+        );
+
+        manager
+            .update_resource(iron, |resource| resource.name = "Steel".to_string())
+            .unwrap();
+        assert_eq!(manager.resource_id_by_name("Steel"), Some(iron));
+        assert!(manager.resource_by_name("Iron").is_none());
+        assert_eq!(manager.prefix_search("Ste").len(), 1);
+
+        assert!(manager
+            .update_resource(iron, |resource| resource.name = "Copper".to_string())
+            .is_err());
+        assert_eq!(manager.resource_by_id(iron).unwrap().name(), "Steel");
+        assert_ne!(manager.resource_id_by_name("Copper"), Some(iron));
+        assert!(manager.update_resource(999, |_| {}).is_err());
+
+        manager
+            .update_resource(iron, |resource| resource.symbol = Some("St".to_string()))
+            .unwrap();
+        assert_eq!(
+            manager.resource_by_name("Steel").unwrap().symbol(),
+            Some("St")
+        );
+
+        manager
+            .resource_by_id_mut(iron)
+            .unwrap()
+            .set_tags(vec![ResourceTag::Metal]);
+        assert!(manager.resources_with_tag(&fuel).is_empty());
+        assert!(manager
+            .resources_with_tag(&ResourceTag::Metal)
+            .iter()
+            .any(|res| res.id() == iron));
+
+        let mut renamed = manager.resource_by_id_mut(iron).unwrap();
+        renamed.name = "Copper".to_string();
+        assert_eq!(
+            renamed.commit(),
+            Err(ResourceError::AlreadyExists("Copper".to_string()))
+        );
+        manager.resource_by_id_mut(iron).unwrap().name = "Gold".to_string();
+        assert_eq!(manager.resource_by_id(iron).unwrap().name(), "Steel");
+        assert!(manager.resource_by_id_mut(999).is_none());
+    }
+
+    #[test]
+    fn tags_are_added_and_removed_once() {
+        let mut tin = ResourceBuilder::new()
+            .name("Tin")
+            .tag(ResourceTag::Metal)
+            .build()
+            .unwrap();
+        tin.add_tag(ResourceTag::Metal);
+        tin.add_tag(ResourceTag::Base);
+        assert_eq!(tin.tags(), &vec![ResourceTag::Metal, ResourceTag::Base]);
+
+        assert!(tin.remove_tag(&ResourceTag::Metal));
+        assert!(!tin.remove_tag(&ResourceTag::Metal));
+        assert!(!tin.remove_tag(&ResourceTag::Ore));
+        assert_eq!(tin.tags(), &vec![ResourceTag::Base]);
+
+        tin.set_tags(vec![ResourceTag::Ore, ResourceTag::Metal, ResourceTag::Ore]);
+        assert_eq!(tin.tags(), &vec![ResourceTag::Ore, ResourceTag::Metal]);
+
+        let mut manager = ResourceManager::new();
+        let id = manager.add_resource(tin).unwrap();
+        manager
+            .update_resource(id, |tin| {
+                tin.remove_tag(&ResourceTag::Ore);
+            })
+            .unwrap();
+        assert!(manager.resources_with_tag(&ResourceTag::Ore).is_empty());
+        assert_eq!(manager.resources_with_tag(&ResourceTag::Metal).len(), 1);
+    }
+
+    #[test]
+    fn named_captures_are_extracted() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let ingots = Regex::new(r"^(?P<metal>\w+) Ingot$").unwrap();
+        let metals = manager
+            .resources_by_named_capture(&ingots, "metal")
+            .into_iter()
+            .map(|(resource, metal)| (resource.name().as_str(), metal))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            metals,
+            vec![
+                ("Iron Ingot", "Iron".to_string()),
+                ("Copper Ingot", "Copper".to_string()),
+                ("Gold Ingot", "Gold".to_string()),
+            ]
+        );
+
+        let optional = Regex::new(r"^Iron(?: (?P<form>Ore|Wire))?$").unwrap();
+        let forms = manager.resources_by_named_capture(&optional, "form");
+        assert_eq!(forms.len(), 2);
+        assert!(forms
+            .iter()
+            .all(|(resource, form)| resource.name() == &format!("Iron {}", form)));
+        assert!(manager
+            .resources_by_named_capture(&ingots, "alloy")
+            .is_empty());
+    }
+
+    #[test]
+    fn search_ranks_exact_then_prefix_then_substring() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let names = |query| {
+            manager
+                .search(query)
+                .into_iter()
+                .map(|resource| resource.name().as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names("iron"),
+            vec!["Iron", "Iron Ingot", "Iron Ore", "Iron Plate", "Iron Wire"]
+        );
+        assert_eq!(
+            names("ING"),
+            vec!["Copper Ingot", "Gold Ingot", "Iron Ingot"]
+        );
+        assert_eq!(names("gold wire"), vec!["Gold Wire"]);
+        assert!(names("Unobtainium").is_empty());
+        assert!(names("").is_empty());
+    }
+
+    #[test]
+    fn bulk_add_reports_each_result() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let metal = |name: &str| {
+            ResourceBuilder::new()
+                .name(name)
+                .tags(vec![ResourceTag::Metal, ResourceTag::Base])
+                .build()
+                .unwrap()
+        };
+        let results = manager.add_resources(vec![metal("Tin"), metal("Iron"), metal("Lead")]);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], Ok(manager.resource_id_by_name("Tin").unwrap()));
+        assert_eq!(
+            results[1],
+            Err((
+                metal("Iron"),
+                ResourceError::AlreadyExists("Iron".to_string())
+            ))
+        );
+        assert_eq!(results[2], Ok(manager.resource_id_by_name("Lead").unwrap()));
+        assert!(manager.resource_by_name("Tin Ingot").is_some());
+        assert!(manager.resource_by_name("Lead Wire").is_some());
+    }
+
+    #[test]
+    fn manager_counts_resources() {
+        let mut manager = ResourceManager::new();
+        assert!(manager.is_empty());
+        setup_resource_manager(&mut manager).unwrap();
+        // Each base metal along with its ingot, plate, ore and wire
+        assert_eq!(manager.len(), 3 * 5);
+        assert!(!manager.is_empty());
+        assert!(manager.contains_name("Gold Plate"));
+        assert!(!manager.contains_name("gold plate"));
+
+        let gold = manager.resource_id_by_name("Gold").unwrap();
+        assert!(manager.contains_id(gold));
+        manager.remove_resource(gold);
+        assert!(!manager.contains_id(gold));
+        assert_eq!(manager.len(), 3 * 5 - 1);
+    }
+
+    #[test]
+    fn transformers_are_listed_and_removed() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let ids = manager
+            .transformers()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            manager.transformers(),
+            vec![
+                (ids[0], Some("Ingot")),
+                (ids[1], Some("Plate")),
+                (ids[2], Some("Ore")),
+                (ids[3], Some("Wire")),
+            ]
+        );
+
+        let enchanter = manager
+            .add_processed_transformer_with(enchant, true)
+            .unwrap();
+        assert_eq!(manager.transformers().last(), Some(&(enchanter, None)));
+
+        let wire = ids[3];
+        assert!(manager.remove_transformer(wire));
+        assert!(!manager.remove_transformer(wire));
+        assert!(manager.resource_by_name("Iron Wire").is_some());
+        assert_eq!(manager.derived_name_patterns().len(), 3);
+        let tin = ResourceBuilder::new()
+            .name("Tin")
+            .tags(vec![ResourceTag::Metal, ResourceTag::Base])
+            .build()
+            .unwrap();
+        manager.add_resource(tin).unwrap();
+        assert!(manager.resource_by_name("Tin Wire").is_none());
+        assert!(manager.resource_by_name("Enchanted Tin Ingot").is_some());
+
+        let ingot = manager.resource_id_by_name("Copper Ingot").unwrap();
+        manager.remove_resource(ingot);
+        let enchanted = manager
+            .resource_id_by_name("Enchanted Copper Ingot")
+            .unwrap();
+        manager.remove_resource(enchanted);
+        let added = manager.reapply_all_transformers().unwrap();
+        let id = |name| manager.resource_id_by_name(name).unwrap();
+        assert_eq!(
+            added,
+            vec![id("Copper Ingot"), id("Enchanted Copper Ingot")]
+        );
+        assert!(manager.reapply_all_transformers().unwrap().is_empty());
+    }
+
+    fn alloy(first: &Resource, second: &Resource) -> Option<Resource> {
+        let base = [ResourceTag::Metal, ResourceTag::Base];
+        if first.contains_all_tags(&base)
+            && second.contains_all_tags(&base)
+            && first.id() < second.id()
+        {
             Some(Resource::new(
-                format!("{} Wire", resource.name()),
-                format!("{} thin and noodly, just the way I like it", resource.name),
+                format!("{}-{} Alloy", first.name(), second.name()),
                 "",
-                resource.fg_color,
-                resource.bg_color,
+                "",
+                first.fg_color,
+                second.bg_color,
                 &[ResourceTag::Metal],
             ))
         } else {
             None
         }
-    })?;
-    /*
-    manager.add_processed_transformer(|resource| {
-        ProcessedResource::new(None, Some("Ore"), resource.clone(), "The ore form!", "")
-    });
-    manager.add_processed_transformer(|resource| {
-        ProcessedResource::new(
-            None,
-            Some("Plate"),
-            resource.clone(),
-            "Flattened to the MAXIMUM extent",
-            "",
-        )
-    });
+    }
 
-     */
+    #[test]
+    fn pair_transformer_makes_alloys() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let alloys = manager.add_pair_transformer(alloy).unwrap();
+        assert!(manager.resource_by_name("Iron-Copper Alloy").is_some());
+        assert!(manager.resource_by_name("Copper-Iron Alloy").is_none());
+        assert!(manager
+            .resource_by_name("Iron Ingot-Iron Plate Alloy")
+            .is_none());
+        assert!(manager
+            .resource_by_name("Iron-Copper Alloy")
+            .unwrap()
+            .is_derived());
 
-    Ok(())
-}
+        let tin = ResourceBuilder::new()
+            .name("Tin")
+            .tags(vec![ResourceTag::Metal, ResourceTag::Base])
+            .build()
+            .unwrap();
+        manager.add_resource(tin).unwrap();
+        assert!(manager.resource_by_name("Iron-Tin Alloy").is_some());
+        assert!(manager.resource_by_name("Copper-Tin Alloy").is_some());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert!(manager.remove_transformer(alloys));
+        let bronze = manager.resource_id_by_name("Copper-Tin Alloy").unwrap();
+        manager.remove_resource(bronze);
+        assert!(manager.reapply_all_transformers().unwrap().is_empty());
+    }
 
     #[test]
-    fn transformations_work() {
+    fn failed_pair_transformer_adds_nothing() {
         let mut manager = ResourceManager::new();
         setup_resource_manager(&mut manager).unwrap();
-        println!("{:?}", manager);
-        assert!(
-            manager.resource_by_name("Iron").is_some(),
-            "Iron wasn't created"
+        let metals = manager.resources_with_tag(&ResourceTag::Metal).len();
+        // Room for one alloy, but iron, copper and gold make three
+        manager.set_tag_limit(ResourceTag::Metal, metals + 1);
+        let resources = manager.len();
+        let transformers = manager.transformers().len();
+
+        assert!(manager.add_pair_transformer(alloy).is_err());
+        assert_eq!(manager.len(), resources);
+        assert_eq!(manager.transformers().len(), transformers);
+        assert!(manager.resource_by_name("Iron-Copper Alloy").is_none());
+    }
+
+    #[test]
+    fn resources_sort_by_tier_and_ingots_inherit_it() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let mithril = ResourceBuilder::new()
+            .name("Mithril")
+            .tags(vec![ResourceTag::Metal, ResourceTag::Base])
+            .tier(2)
+            .build()
+            .unwrap();
+        manager.add_resource(mithril).unwrap();
+        assert_eq!(manager.resource_by_name("Mithril Ingot").unwrap().tier(), 3);
+        assert_eq!(manager.resource_by_name("Mithril Plate").unwrap().tier(), 0);
+
+        let names = |resources: Vec<&Resource>| {
+            resources
+                .into_iter()
+                .map(|res| res.name().clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(manager.resources_by_tier(1)),
+            vec!["Iron Ingot", "Copper Ingot", "Gold Ingot"]
         );
-        assert!(manager.resource_by_name("Iron Plate").is_some());
-        assert!(manager.resource_by_name("Iron Ingot").is_some());
+        let sorted = manager.resources_sorted(ResourceOrder::Tier);
+        assert_eq!(
+            names(sorted[sorted.len() - 5..].to_vec()),
+            vec![
+                "Copper Ingot",
+                "Gold Ingot",
+                "Iron Ingot",
+                "Mithril",
+                "Mithril Ingot"
+            ]
+        );
+
+        let def: ResourceDef = serde_json::from_str(r#"{"name": "Adamant", "tier": 4}"#).unwrap();
+        assert_eq!(Resource::from(def).tier(), 4);
+    }
+
+    #[test]
+    fn managers_merge_with_new_ids() {
+        let mut base = ResourceManager::new();
+        setup_resource_manager(&mut base).unwrap();
+        let metal = |name: &str| {
+            ResourceBuilder::new()
+                .name(name)
+                .tags(vec![ResourceTag::Metal, ResourceTag::Base])
+                .build()
+                .unwrap()
+        };
+        let mod_resources = || {
+            let mut other = ResourceManager::new();
+            other.add_resource(metal("Mithril")).unwrap();
+            other.add_resource(metal("Iron")).unwrap();
+            other
+        };
+
+        let other = mod_resources();
+        let mithril = other.resource_id_by_name("Mithril").unwrap();
+        let iron = other.resource_id_by_name("Iron").unwrap();
+        let before = base.len();
+        assert_eq!(
+            base.merge(other).unwrap_err(),
+            "Resources already exist: Iron"
+        );
+        assert_eq!(base.len(), before);
+
+        let (ids, collisions) = base
+            .merge_with(mod_resources(), MergeCollision::KeepExisting)
+            .unwrap();
+        assert_eq!(collisions, vec![iron]);
+        assert_eq!(ids[&iron], base.resource_id_by_name("Iron").unwrap());
+        assert_eq!(
+            base.resource_by_id(ids[&mithril]).unwrap().name(),
+            "Mithril"
+        );
+        assert_ne!(ids[&mithril], mithril);
+        // Only Mithril is new, and the transformers haven't run on it yet
+        assert_eq!(base.len(), before + 1);
+        assert!(base.resource_by_name("Mithril Ingot").is_none());
+        base.reapply_all_transformers().unwrap();
+        assert!(base.resource_by_name("Mithril Ingot").is_some());
+    }
+
+    #[test]
+    fn hashed_ids_ignore_insertion_order() {
+        let load = |base_metals: &[BaseMetalSpec]| {
+            let mut manager = ResourceManager::new();
+            manager.set_id_assignment(IdAssignment::NameHash);
+            setup_resource_manager_with(&mut manager, base_metals).unwrap();
+            manager
+                .resources()
+                .map(|res| (res.name().clone(), res.id()))
+                .collect::<HashMap<_, _>>()
+        };
+        let mut reversed = default_base_metals();
+        reversed.reverse();
+        let ids = load(&default_base_metals());
+        assert_eq!(ids.len(), 3 * 5);
+        assert_eq!(ids, load(&reversed));
+        assert_eq!(ids["Iron"], stable_name_hash("Iron"));
+
+        let mut manager = ResourceManager::new();
+        manager.set_id_assignment(IdAssignment::NameHash);
+        let tin = Resource::new("Tin", "", "", Color::WHITE, Color::BLACK, &[]);
+        let id = manager.add_resource(tin.clone()).unwrap();
+        manager.remove_resource(id);
+        assert_eq!(manager.add_resource(tin).unwrap(), id);
+
+        let mut manager = ResourceManager::new();
+        manager.set_id_assignment(IdAssignment::NameHash);
+        manager.name_hash_override = Some(|_| u64::MAX);
+        let metal = |name| Resource::new(name, "", "", Color::WHITE, Color::BLACK, &[]);
+        let lead = manager.add_resource(metal("Lead")).unwrap();
+        assert_eq!(lead, u64::MAX);
+        // Every name collides, so the next free id is taken, wrapping around
+        assert_eq!(manager.add_resource(metal("Zinc")).unwrap(), 0);
+        manager.remove_resource(lead);
+        assert_eq!(manager.add_resource(metal("Nickel")).unwrap(), lead);
+    }
+
+    #[test]
+    fn regex_patterns_are_compiled_once() {
+        let mut manager = ResourceManager::new();
+        setup_resource_manager(&mut manager).unwrap();
+        let names = |found: Vec<(&Resource, Captures)>| {
+            found
+                .into_iter()
+                .map(|(res, _)| res.name().clone())
+                .collect::<Vec<_>>()
+        };
+
+        let first = names(
+            manager
+                .resources_by_regular_expression_str("^Gold")
+                .unwrap(),
+        );
+        assert_eq!(manager.regex_cache().misses(), 1);
+        let second = names(
+            manager
+                .resources_by_regular_expression_str("^Gold")
+                .unwrap(),
+        );
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 5);
+        assert_eq!(manager.regex_cache().hits(), 1);
+        assert_eq!(manager.regex_cache().misses(), 1);
+
+        assert!(manager
+            .resources_by_regular_expression_str("[Gold")
+            .is_err());
+        assert_eq!(manager.regex_cache().len(), 1);
     }
 }
@@ -0,0 +1,145 @@
+use crate::production::resource::Resource;
+
+type Predicate = Box<dyn Fn(&Resource) -> bool>;
+type Producer = Box<dyn Fn(&Resource) -> Option<Resource>>;
+
+/// Builds a processed-resource transformer out of predicates and producers, for use with
+/// [`ResourceManager::add_processed_transformer`](crate::production::resource::ResourceManager::add_processed_transformer)
+pub struct TransformerBuilder {
+    predicates: Vec<Predicate>,
+    producers: Vec<Producer>,
+}
+
+impl TransformerBuilder {
+    pub fn new() -> Self {
+        Self {
+            predicates: vec![],
+            producers: vec![],
+        }
+    }
+
+    /// Only transforms resources matching the predicate. Multiple predicates must all match.
+    pub fn when<P>(mut self, predicate: P) -> Self
+    where
+        P: 'static + Fn(&Resource) -> bool,
+    {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Adds a producer that always creates a resource from a matching one
+    pub fn produce<F>(self, producer: F) -> Self
+    where
+        F: 'static + Fn(&Resource) -> Resource,
+    {
+        self.chain(move |resource| Some(producer(resource)))
+    }
+
+    /// Adds a producer that may decline to create a resource. Producers are tried in the order
+    /// they were added, and the first one that creates a resource wins.
+    pub fn chain<F>(mut self, producer: F) -> Self
+    where
+        F: 'static + Fn(&Resource) -> Option<Resource>,
+    {
+        self.producers.push(Box::new(producer));
+        self
+    }
+
+    pub fn build(self) -> impl Fn(&Resource) -> Option<Resource> {
+        let TransformerBuilder {
+            predicates,
+            producers,
+        } = self;
+        move |resource| {
+            if !predicates.iter().all(|predicate| predicate(resource)) {
+                return None;
+            }
+            producers.iter().find_map(|producer| producer(resource))
+        }
+    }
+}
+
+impl Default for TransformerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::production::resource::ResourceTag;
+    use iced::Color;
+
+    fn iron() -> Resource {
+        Resource::new(
+            "Iron",
+            "A common, sturdy metal",
+            "",
+            Color::WHITE,
+            Color::BLACK,
+            &[ResourceTag::Metal, ResourceTag::Base],
+        )
+    }
+
+    #[test]
+    fn builder_matches_inline_ingot_transformer() {
+        let inline = |resource: &Resource| {
+            if resource.contains_all_tags(&[ResourceTag::Base, ResourceTag::Metal]) {
+                Some(Resource::new(
+                    format!("{} Ingot", resource.name()),
+                    format!("A pure form of {} in a convenient bar form", resource.name()),
+                    "",
+                    *resource.fg_color(),
+                    *resource.bg_color(),
+                    &[ResourceTag::Metal, ResourceTag::Ingot],
+                ))
+            } else {
+                None
+            }
+        };
+        let built = TransformerBuilder::new()
+            .when(|resource| resource.contains_all_tags(&[ResourceTag::Base, ResourceTag::Metal]))
+            .produce(|resource| {
+                Resource::new(
+                    format!("{} Ingot", resource.name()),
+                    format!("A pure form of {} in a convenient bar form", resource.name()),
+                    "",
+                    *resource.fg_color(),
+                    *resource.bg_color(),
+                    &[ResourceTag::Metal, ResourceTag::Ingot],
+                )
+            })
+            .build();
+
+        let expected = inline(&iron()).unwrap();
+        let actual = built(&iron()).unwrap();
+        assert_eq!(actual.name(), expected.name());
+        assert_eq!(actual.description(), expected.description());
+        assert_eq!(actual.tags(), expected.tags());
+        assert_eq!(actual.fg_color(), expected.fg_color());
+        assert_eq!(actual.bg_color(), expected.bg_color());
+
+        let ingot = actual;
+        assert!(inline(&ingot).is_none());
+        assert!(built(&ingot).is_none());
+    }
+
+    #[test]
+    fn chain_falls_through_to_next_producer() {
+        let transformer = TransformerBuilder::new()
+            .chain(|_| None)
+            .chain(|resource| {
+                Some(Resource::new(
+                    format!("{} Dust", resource.name()),
+                    "",
+                    "",
+                    Color::WHITE,
+                    Color::BLACK,
+                    &[],
+                ))
+            })
+            .build();
+        assert_eq!(transformer(&iron()).unwrap().name(), "Iron Dust");
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: BTreeMap<char, usize>,
+    ids: Vec<u64>,
+}
+
+/// A prefix tree over names, mapping each name to the ids stored under it
+#[derive(Debug)]
+pub struct NameTrie {
+    nodes: Vec<TrieNode>,
+}
+
+impl NameTrie {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![TrieNode::default()],
+        }
+    }
+
+    pub fn insert(&mut self, name: &str, id: u64) {
+        let mut current = 0;
+        for c in name.chars() {
+            current = match self.nodes[current].children.get(&c) {
+                Some(&next) => next,
+                None => {
+                    self.nodes.push(TrieNode::default());
+                    let next = self.nodes.len() - 1;
+                    self.nodes[current].children.insert(c, next);
+                    next
+                }
+            };
+        }
+        self.nodes[current].ids.push(id);
+    }
+
+    /// Removes an id stored under a name, returning whether it was present
+    pub fn remove(&mut self, name: &str, id: u64) -> bool {
+        match self.find(name) {
+            Some(node) => {
+                let ids = &mut self.nodes[node].ids;
+                let before = ids.len();
+                ids.retain(|&stored| stored != id);
+                ids.len() != before
+            }
+            None => false,
+        }
+    }
+
+    fn find(&self, prefix: &str) -> Option<usize> {
+        let mut current = 0;
+        for c in prefix.chars() {
+            current = *self.nodes[current].children.get(&c)?;
+        }
+        Some(current)
+    }
+
+    /// Gets the ids of every name starting with the prefix, ordered by name
+    pub fn with_prefix(&self, prefix: &str) -> Vec<u64> {
+        let mut ids = Vec::new();
+        let mut stack = match self.find(prefix) {
+            Some(node) => vec![node],
+            None => return ids,
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            ids.extend(&node.ids);
+            stack.extend(node.children.values().rev());
+        }
+        ids
+    }
+}
+
+impl Default for NameTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_results_are_ordered_by_name() {
+        let mut trie = NameTrie::new();
+        trie.insert("Iron Plate", 2);
+        trie.insert("Iron", 0);
+        trie.insert("Gold", 3);
+        trie.insert("Iron Ingot", 1);
+        assert_eq!(trie.with_prefix("Iron"), vec![0, 1, 2]);
+        assert_eq!(trie.with_prefix("Iron P"), vec![2]);
+        assert_eq!(trie.with_prefix(""), vec![3, 0, 1, 2]);
+        assert!(trie.with_prefix("Copper").is_empty());
+
+        assert!(trie.remove("Iron Ingot", 1));
+        assert!(!trie.remove("Iron Ingot", 1));
+        assert_eq!(trie.with_prefix("Iron"), vec![0, 2]);
+    }
+}
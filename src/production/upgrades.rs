@@ -0,0 +1,198 @@
+use crate::processing::recipe::RecipeComponent;
+use crate::production::inventory::Inventory;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// What an [`Upgrade`] does once it's bought
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpgradeEffect {
+    /// Multiplies how much of a resource a click crafts
+    ClickMultiplier { resource_id: u64, multiplier: f64 },
+}
+
+/// Something the player can buy once to improve their production
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upgrade {
+    /// The resources taken when the upgrade is bought
+    pub cost: Vec<RecipeComponent>,
+    pub effect: UpgradeEffect,
+}
+
+/// Why an upgrade couldn't be bought
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeError {
+    /// No upgrade has the id
+    NotFound(u64),
+    /// The upgrade was already bought
+    AlreadyBought(u64),
+    /// Less of a resource was on hand than the upgrade costs
+    Insufficient {
+        resource_id: u64,
+        required: usize,
+        available: usize,
+    },
+}
+
+impl Display for UpgradeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpgradeError::NotFound(id) => write!(f, "No upgrade with id {}", id),
+            UpgradeError::AlreadyBought(id) => write!(f, "Upgrade {} was already bought", id),
+            UpgradeError::Insufficient {
+                resource_id,
+                required,
+                available,
+            } => write!(
+                f,
+                "Needed {} of resource #{} but only {} are available",
+                required, resource_id, available
+            ),
+        }
+    }
+}
+
+impl Error for UpgradeError {}
+
+/// The upgrades on offer, by id, and which of them have been bought. Bought upgrades stay bought
+/// even if they're no longer offered, but only offered upgrades have an effect.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Upgrades {
+    offered: BTreeMap<u64, Upgrade>,
+    bought: BTreeSet<u64>,
+}
+
+impl Upgrades {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Offers an upgrade under an id, replacing any upgrade already offered under it
+    pub fn insert(&mut self, id: u64, upgrade: Upgrade) {
+        self.offered.insert(id, upgrade);
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Upgrade> {
+        self.offered.get(&id)
+    }
+
+    pub fn is_bought(&self, id: u64) -> bool {
+        self.bought.contains(&id)
+    }
+
+    /// Gets the ids of the bought upgrades, in order
+    pub fn bought(&self) -> impl Iterator<Item = u64> + '_ {
+        self.bought.iter().copied()
+    }
+
+    /// Marks an upgrade as bought without paying for it, such as when loading a game
+    pub(crate) fn restore_bought(&mut self, id: u64) {
+        self.bought.insert(id);
+    }
+
+    /// Buys an upgrade, taking its cost from the inventory. On failure the inventory is left
+    /// unchanged.
+    pub fn buy(&mut self, id: u64, inventory: &mut Inventory) -> Result<(), UpgradeError> {
+        let upgrade = self.offered.get(&id).ok_or(UpgradeError::NotFound(id))?;
+        if self.bought.contains(&id) {
+            return Err(UpgradeError::AlreadyBought(id));
+        }
+        let mut cost = BTreeMap::new();
+        for component in &upgrade.cost {
+            *cost.entry(component.resource_id()).or_insert(0) += component.quantity();
+        }
+        for (&resource_id, &required) in &cost {
+            let available = inventory.quantity(resource_id);
+            if available < required {
+                return Err(UpgradeError::Insufficient {
+                    resource_id,
+                    required,
+                    available,
+                });
+            }
+        }
+        for (resource_id, required) in cost {
+            inventory.set(resource_id, inventory.quantity(resource_id) - required);
+        }
+        self.bought.insert(id);
+        Ok(())
+    }
+
+    /// How much a click's crafts of a resource are multiplied by, the product of the multipliers
+    /// of every bought upgrade for it
+    pub fn click_multiplier(&self, resource_id: u64) -> f64 {
+        self.bought
+            .iter()
+            .filter_map(|id| self.offered.get(id))
+            .map(|upgrade| match upgrade.effect {
+                UpgradeEffect::ClickMultiplier {
+                    resource_id: upgraded,
+                    multiplier,
+                } if upgraded == resource_id => multiplier,
+                _ => 1.0,
+            })
+            .product()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doubler(resource_id: u64, cost: usize) -> Upgrade {
+        Upgrade {
+            cost: vec![RecipeComponent::new(0, cost)],
+            effect: UpgradeEffect::ClickMultiplier {
+                resource_id,
+                multiplier: 2.0,
+            },
+        }
+    }
+
+    #[test]
+    fn buying_takes_the_cost_once() {
+        let mut upgrades = Upgrades::new();
+        upgrades.insert(0, doubler(1, 3));
+        let mut inventory = Inventory::new();
+        inventory.set(0, 2);
+
+        assert_eq!(
+            upgrades.buy(0, &mut inventory),
+            Err(UpgradeError::Insufficient {
+                resource_id: 0,
+                required: 3,
+                available: 2
+            })
+        );
+        assert_eq!(inventory.quantity(0), 2);
+        assert_eq!(
+            upgrades.buy(1, &mut inventory),
+            Err(UpgradeError::NotFound(1))
+        );
+
+        inventory.set(0, 5);
+        assert_eq!(upgrades.buy(0, &mut inventory), Ok(()));
+        assert_eq!(inventory.quantity(0), 2);
+        assert!(upgrades.is_bought(0));
+        assert_eq!(
+            upgrades.buy(0, &mut inventory),
+            Err(UpgradeError::AlreadyBought(0))
+        );
+        assert_eq!(inventory.quantity(0), 2);
+    }
+
+    #[test]
+    fn multipliers_stack_per_resource() {
+        let mut upgrades = Upgrades::new();
+        upgrades.insert(0, doubler(1, 0));
+        upgrades.insert(1, doubler(1, 0));
+        upgrades.insert(2, doubler(2, 0));
+        let mut inventory = Inventory::new();
+        assert_eq!(upgrades.click_multiplier(1), 1.0);
+
+        upgrades.buy(0, &mut inventory).unwrap();
+        upgrades.buy(1, &mut inventory).unwrap();
+        assert_eq!(upgrades.click_multiplier(1), 4.0);
+        assert_eq!(upgrades.click_multiplier(2), 1.0);
+    }
+}